@@ -0,0 +1,12 @@
+RWTexture2D<float4> output_tex;
+
+// Written to the outputs of passes which failed to record, e.g. due to shader compile errors.
+
+// clang-format off
+[numthreads(8, 8, 1)]
+void main(in uint2 pix : SV_DispatchThreadID) {
+    // clang-format on
+    uint2 cell = pix / 16;
+    bool odd = ((cell.x + cell.y) & 1) != 0;
+    output_tex[pix] = odd ? float4(1.0, 0.0, 1.0, 1.0) : float4(0.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,220 @@
+use crate::resource::SubresourceRange;
+use render_core::{handles::RenderResourceHandle, types::RenderResourceStates};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResourceBarrier {
+    pub resource: RenderResourceHandle,
+    /// `None` if the state of the resource is not known, e.g. because it's just been created
+    pub before: Option<RenderResourceStates>,
+    pub after: RenderResourceStates,
+}
+
+pub(crate) fn is_read_only(state: RenderResourceStates) -> bool {
+    let read_only_states = RenderResourceStates::PIXEL_SHADER_RESOURCE
+        | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE
+        | RenderResourceStates::INDIRECT_ARGUMENT
+        | RenderResourceStates::COPY_SOURCE
+        | RenderResourceStates::DEPTH_READ;
+
+    read_only_states.contains(state)
+}
+
+/// A state which can be used for accesses in both `a` and `b` within one pass,
+/// e.g. when reading one mip of a texture while writing another.
+fn merge_states(a: RenderResourceStates, b: RenderResourceStates) -> RenderResourceStates {
+    if is_read_only(a) && is_read_only(b) {
+        a | b
+    } else if a == b {
+        a
+    } else {
+        // Mixed reads and writes. Unordered access maps to the general image layout,
+        // which every other kind of access can use as well.
+        RenderResourceStates::UNORDERED_ACCESS
+    }
+}
+
+#[derive(Default)]
+struct TrackedResource {
+    state: Option<RenderResourceStates>,
+    // Accesses since the last barrier on the resource. Accesses to other subresources
+    // don't need to be synchronized with these.
+    unsynchronized: Vec<(SubresourceRange, RenderResourceStates)>,
+}
+
+/// Tracks the state of GPU resources across passes, and works out which transitions are
+/// actually needed. State is tracked per GPU resource rather than per graph resource,
+/// so that aliased resources pick up where the previous user left off.
+///
+/// render-core can only transition whole resources, so all subresources share one state,
+/// but accesses are tracked per subresource range. This way passes touching disjoint
+/// mips or slices of a resource in the same state don't need barriers between them.
+#[derive(Default)]
+pub(crate) struct ResourceStateTracker {
+    resources: HashMap<RenderResourceHandle, TrackedResource>,
+    // Accesses in the batch being built; see `end_batch`.
+    batch_accesses: Vec<(RenderResourceHandle, SubresourceRange, RenderResourceStates)>,
+}
+
+impl ResourceStateTracker {
+    pub fn set_state(&mut self, resource: RenderResourceHandle, state: RenderResourceStates) {
+        self.resources.insert(
+            resource,
+            TrackedResource {
+                state: Some(state),
+                unsynchronized: Vec::new(),
+            },
+        );
+    }
+
+    pub fn state(&self, resource: RenderResourceHandle) -> Option<RenderResourceStates> {
+        self.resources
+            .get(&resource)
+            .and_then(|tracked| tracked.state)
+    }
+
+    /// Makes sure `subresources` of `resource` can be accessed in `state`, pushing a barrier
+    /// into `barriers` if needed. Barriers for one resource within the same batch are merged.
+    /// Call `end_batch` once all accesses of the batch have been submitted.
+    pub fn require(
+        &mut self,
+        resource: RenderResourceHandle,
+        subresources: SubresourceRange,
+        state: RenderResourceStates,
+        barriers: &mut Vec<ResourceBarrier>,
+    ) {
+        let tracked = self.resources.entry(resource).or_default();
+        let batch_barrier = barriers.iter().position(|b| b.resource == resource);
+
+        let needs_barrier = batch_barrier.is_some()
+            || match tracked.state {
+                Some(current) => {
+                    let state_compatible = if is_read_only(current) {
+                        current.contains(state)
+                    } else {
+                        current == state
+                    };
+
+                    // Anything but read-after-read on overlapping subresources needs
+                    // a barrier, including write-after-write in the same state,
+                    // which still needs to order the writes.
+                    let hazard = tracked.unsynchronized.iter().any(|(range, prev_state)| {
+                        range.overlaps(&subresources)
+                            && !(is_read_only(*prev_state) && is_read_only(state))
+                    });
+
+                    !state_compatible || hazard
+                }
+                None => true,
+            };
+
+        if needs_barrier {
+            // The barrier goes before the whole batch, so all accesses in the batch
+            // need to be able to use the new state.
+            let after = self
+                .batch_accesses
+                .iter()
+                .filter(|(batch_resource, ..)| *batch_resource == resource)
+                .fold(state, |after, (_, _, batch_state)| {
+                    merge_states(after, *batch_state)
+                });
+
+            let after = match tracked.state {
+                // Reads in different states are merged, so that subsequent reads
+                // in either state don't need another transition.
+                Some(current) if is_read_only(current) && is_read_only(after) => current | after,
+                _ => after,
+            };
+
+            if let Some(barrier_idx) = batch_barrier {
+                barriers[barrier_idx].after = after;
+            } else {
+                barriers.push(ResourceBarrier {
+                    resource,
+                    before: tracked.state,
+                    after,
+                });
+            }
+
+            tracked.state = Some(after);
+        }
+
+        self.batch_accesses.push((resource, subresources, state));
+    }
+
+    /// Commits the accesses submitted via `require` since the last call.
+    /// `barriers` are the ones emitted before the batch.
+    pub fn end_batch(&mut self, barriers: &[ResourceBarrier]) {
+        for barrier in barriers {
+            if let Some(tracked) = self.resources.get_mut(&barrier.resource) {
+                tracked.unsynchronized.clear();
+            }
+        }
+
+        for (resource, subresources, state) in self.batch_accesses.drain(..) {
+            if let Some(tracked) = self.resources.get_mut(&resource) {
+                tracked.unsynchronized.push((subresources, state));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render_core::{handles::RenderResourceHandleAllocator, types::RenderResourceType};
+
+    #[test]
+    fn mixed_accesses_in_one_batch_merge_into_one_whole_resource_barrier() {
+        let texture = RenderResourceHandleAllocator::new().allocate(RenderResourceType::Texture);
+        let mut tracker = ResourceStateTracker::default();
+        tracker.set_state(texture, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+        // A downsample: read mip 0, write mip 1
+        let mut barriers = Vec::new();
+        tracker.require(
+            texture,
+            SubresourceRange::mip(0),
+            RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+            &mut barriers,
+        );
+        tracker.require(
+            texture,
+            SubresourceRange::mip(1),
+            RenderResourceStates::UNORDERED_ACCESS,
+            &mut barriers,
+        );
+        tracker.end_batch(&barriers);
+
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(
+            barriers[0].before,
+            Some(RenderResourceStates::PIXEL_SHADER_RESOURCE)
+        );
+        assert_eq!(barriers[0].after, RenderResourceStates::UNORDERED_ACCESS);
+    }
+
+    #[test]
+    fn writes_to_disjoint_mips_need_no_barrier() {
+        let texture = RenderResourceHandleAllocator::new().allocate(RenderResourceType::Texture);
+        let mut tracker = ResourceStateTracker::default();
+        tracker.set_state(texture, RenderResourceStates::UNORDERED_ACCESS);
+
+        let mut write = |mip| {
+            let mut barriers = Vec::new();
+            tracker.require(
+                texture,
+                SubresourceRange::mip(mip),
+                RenderResourceStates::UNORDERED_ACCESS,
+                &mut barriers,
+            );
+            tracker.end_batch(&barriers);
+            barriers.len()
+        };
+
+        assert_eq!(write(0), 0);
+        assert_eq!(write(1), 0);
+        // Write-after-write on mip 0 still needs ordering
+        assert_eq!(write(0), 1);
+    }
+}
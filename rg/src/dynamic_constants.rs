@@ -6,7 +6,7 @@ use render_core::{
 };
 use std::{
     mem::size_of,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 
 const CHUNK_SIZE: usize = 64 * 1024;
@@ -57,27 +57,24 @@ impl IntoConstantBufferWithOffset for DynamicConstantsAllocation {
     }
 }
 
-pub struct DynamicConstants {
+#[derive(Default)]
+struct Chunks {
     chunks: Vec<Chunk>,
     free_chunks: Vec<Chunk>,
-    handles: Arc<RwLock<RenderResourceHandleAllocator>>,
 }
 
-impl DynamicConstants {
-    pub fn new(handles: Arc<RwLock<RenderResourceHandleAllocator>>) -> Self {
-        Self {
-            chunks: Default::default(),
-            free_chunks: Default::default(),
-            handles,
-        }
-    }
+/// Per-frame constant data. Can be pushed to from multiple threads recording passes.
+pub struct DynamicConstants {
+    chunks: Mutex<Chunks>,
+    handles: Arc<RwLock<RenderResourceHandleAllocator>>,
+}
 
-    fn alloc_chunk(&mut self) {
+impl Chunks {
+    fn alloc_chunk(&mut self, handles: &RwLock<RenderResourceHandleAllocator>) {
         if !self.free_chunks.is_empty() {
             self.chunks.push(self.free_chunks.pop().unwrap());
         } else {
-            let buffer = self
-                .handles
+            let buffer = handles
                 .write()
                 .unwrap()
                 .allocate(RenderResourceType::Buffer);
@@ -89,13 +86,24 @@ impl DynamicConstants {
             });
         }
     }
+}
+
+impl DynamicConstants {
+    pub fn new(handles: Arc<RwLock<RenderResourceHandleAllocator>>) -> Self {
+        Self {
+            chunks: Default::default(),
+            handles,
+        }
+    }
 
     pub fn commit_and_reset(
         &mut self,
         command_list: &mut RenderCommandList<'_>,
         device: &dyn RenderDevice,
     ) {
-        for chunk in self.chunks.iter_mut() {
+        let chunks = self.chunks.get_mut().unwrap();
+
+        for chunk in chunks.chunks.iter_mut() {
             let buffer = match chunk.buffer {
                 ChunkBuffer::Unbacked(handle) => {
                     device
@@ -120,19 +128,21 @@ impl DynamicConstants {
             chunk.write_head = 0;
         }
 
-        self.free_chunks.append(&mut self.chunks);
+        chunks.free_chunks.append(&mut chunks.chunks);
     }
 
-    pub fn push<T: Copy>(&mut self, t: T) -> DynamicConstantsAllocation {
+    pub fn push<T: Copy>(&self, t: T) -> DynamicConstantsAllocation {
         let t_size = size_of::<T>();
         assert!(t_size <= CHUNK_SIZE);
 
-        if self.chunks.is_empty() || self.chunks.last().unwrap().free_space() < t_size {
-            self.alloc_chunk();
+        let mut chunks = self.chunks.lock().unwrap();
+
+        if chunks.chunks.is_empty() || chunks.chunks.last().unwrap().free_space() < t_size {
+            chunks.alloc_chunk(&self.handles);
         }
 
-        let mut chunk = self.chunks.last_mut().unwrap();
-        assert!(chunk.free_space() >= CHUNK_SIZE);
+        let chunk = chunks.chunks.last_mut().unwrap();
+        assert!(chunk.free_space() >= t_size);
 
         let dst = &mut chunk.data[chunk.write_head as usize..chunk.write_head as usize + t_size];
         dst.copy_from_slice(as_byte_slice(&t));
@@ -148,13 +158,28 @@ impl DynamicConstants {
         allocation
     }
 
+    /// Size of the GPU buffers backing the chunks.
+    pub fn allocated_bytes(&self) -> usize {
+        let chunks = self.chunks.lock().unwrap();
+        let backed_chunk_count = chunks
+            .chunks
+            .iter()
+            .chain(chunks.free_chunks.iter())
+            .filter(|chunk| matches!(chunk.buffer, ChunkBuffer::Backed(..)))
+            .count();
+
+        backed_chunk_count * CHUNK_SIZE
+    }
+
     pub fn destroy(&mut self, device: &mut dyn RenderDevice) {
+        let chunks = self.chunks.get_mut().unwrap();
+
         assert!(
-            self.chunks.is_empty(),
+            chunks.chunks.is_empty(),
             "live chunks still present; commit_and_reset() must be called before destroy()"
         );
 
-        for chunk in self.free_chunks.drain(..) {
+        for chunk in chunks.free_chunks.drain(..) {
             if let ChunkBuffer::Backed(buffer) = chunk.buffer {
                 device.destroy_resource(buffer).unwrap();
             } else {
@@ -0,0 +1,52 @@
+use crate::graph::RenderGraph;
+
+/// A reusable group of passes, e.g. bloom or TAA, added to graphs via `RenderGraph::add_feature`.
+///
+/// Features take graph resources as `Inputs`, and hand the resources they produce back
+/// as `Outputs`, so they can be shared between projects without knowing anything about
+/// the rest of the graph. Names of passes, created and temporal resources added by
+/// the feature are prefixed with its `name`.
+pub trait RenderFeature {
+    type Inputs;
+    type Outputs;
+
+    fn name(&self) -> &str;
+
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Adds the passes of the feature to the graph.
+    fn add_passes(&self, rg: &mut RenderGraph, inputs: Self::Inputs) -> Self::Outputs;
+
+    /// What the feature outputs when it's disabled, e.g. its input passed through unchanged.
+    fn bypass(&self, inputs: Self::Inputs) -> Self::Outputs;
+}
+
+impl RenderGraph {
+    /// Adds the passes of `feature`, or bypasses it if it's disabled.
+    pub fn add_feature<F: RenderFeature>(&mut self, feature: &F, inputs: F::Inputs) -> F::Outputs {
+        if feature.enabled() {
+            self.scope(feature.name(), |rg| feature.add_passes(rg, inputs))
+        } else {
+            feature.bypass(inputs)
+        }
+    }
+
+    /// Runs `f` with `name` added to the prefix of pass and resource names, e.g. to turn
+    /// "downsample" into "bloom/downsample". Scopes can be nested.
+    pub fn scope<R>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        let prev_len = self.name_scope.len();
+        self.name_scope.push_str(name);
+        self.name_scope.push('/');
+
+        let result = f(self);
+
+        self.name_scope.truncate(prev_len);
+        result
+    }
+
+    pub(crate) fn scoped_name(&self, name: &str) -> String {
+        format!("{}{}", self.name_scope, name)
+    }
+}
@@ -0,0 +1,161 @@
+use render_core::{device::RenderDevice, handles::RenderResourceHandle};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+// Number of frames timings are averaged over
+const AVERAGE_FRAME_COUNT: usize = 60;
+
+/// Timestamps written around each pass by `RenderGraph::execute`. They can only be read
+/// once the GPU has finished the frame; see `GpuProfiler::read_back`.
+#[must_use]
+pub struct GpuTimestampQueries {
+    pub(crate) heap: RenderResourceHandle,
+    /// Recorded passes in query order; pass `i` uses queries `2 * i` and `2 * i + 1`.
+    pub(crate) passes: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GpuPassTiming {
+    pub name: String,
+    /// Duration of the pass in the most recently read back frame
+    pub last_ms: f64,
+    /// Duration of the pass averaged over recent frames
+    pub average_ms: f64,
+}
+
+/// Collects per-pass GPU timings from the timestamps recorded by graphs with
+/// `RenderGraph::gpu_timestamps` enabled.
+///
+/// Timestamps of passes on the async compute queue are measured against the same clock,
+/// but overlap with graphics work, so per-pass times don't necessarily add up to the frame.
+pub struct GpuProfiler {
+    enabled: bool,
+    timings: Vec<GpuPassTiming>,
+    history: HashMap<String, VecDeque<f64>>,
+    log_interval: Option<Duration>,
+    last_log: Instant,
+}
+
+impl Default for GpuProfiler {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timings: Vec::new(),
+            history: HashMap::new(),
+            log_interval: None,
+            last_log: Instant::now(),
+        }
+    }
+}
+
+impl GpuProfiler {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.timings.clear();
+            self.history.clear();
+        }
+    }
+
+    /// Log the timing table every `interval`, or never if `None`.
+    pub fn log_every(&mut self, interval: Option<Duration>) {
+        self.log_interval = interval;
+    }
+
+    /// Timings of the passes of the most recently read back frame, in recording order.
+    pub fn timings(&self) -> &[GpuPassTiming] {
+        &self.timings
+    }
+
+    /// Reads the timestamps of a frame which the GPU has finished executing,
+    /// and updates the timing table.
+    pub fn read_back(
+        &mut self,
+        device: &dyn RenderDevice,
+        queries: GpuTimestampQueries,
+    ) -> anyhow::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let ticks_per_ms = device.timestamp_frequency()? as f64 / 1000.0;
+        let mut timestamps = vec![0u64; queries.passes.len() * 2];
+        device.read_timing_heap(queries.heap, 0, &mut timestamps)?;
+
+        // Passes which weren't recorded this frame are dropped from the table.
+        let mut history = std::mem::take(&mut self.history);
+        self.timings.clear();
+
+        for (name, timestamps) in queries.passes.into_iter().zip(timestamps.chunks(2)) {
+            let ms = timestamps[1].saturating_sub(timestamps[0]) as f64 / ticks_per_ms;
+
+            let mut samples = history.remove(&name).unwrap_or_default();
+            if samples.len() == AVERAGE_FRAME_COUNT {
+                samples.pop_front();
+            }
+            samples.push_back(ms);
+
+            self.timings.push(GpuPassTiming {
+                name: name.clone(),
+                last_ms: ms,
+                average_ms: samples.iter().sum::<f64>() / samples.len() as f64,
+            });
+            self.history.insert(name, samples);
+        }
+
+        if let Some(interval) = self.log_interval {
+            if self.last_log.elapsed() >= interval {
+                self.last_log = Instant::now();
+                log::info!("{}", self);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for GpuProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self
+            .timings
+            .iter()
+            .map(|timing| timing.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+
+        write!(f, "GPU pass timings (last / average ms):")?;
+        for timing in self.timings.iter() {
+            write!(
+                f,
+                "\n    {:width$}  {:7.3}  {:7.3}",
+                timing.name,
+                timing.last_ms,
+                timing.average_ms,
+                width = name_width
+            )?;
+        }
+
+        write!(
+            f,
+            "\n    {:width$}  {:7.3}  {:7.3}",
+            "total",
+            self.timings
+                .iter()
+                .map(|timing| timing.last_ms)
+                .sum::<f64>(),
+            self.timings
+                .iter()
+                .map(|timing| timing.average_ms)
+                .sum::<f64>(),
+            width = name_width
+        )
+    }
+}
@@ -1,8 +1,20 @@
 #![allow(unused_imports)]
 
 use crate::{
-    pass_builder::PassBuilder, pipeline_cache::PipelineCache, resource::*,
-    resource_registry::ResourceRegistry, shader_cache::*, DynamicConstants,
+    barrier::{ResourceBarrier, ResourceStateTracker},
+    gpu_profiler::GpuTimestampQueries,
+    graph_cache::RenderGraphCache,
+    memory_report::{PersistentAllocation, TransientMemoryReport, TransientResourceMemory},
+    pass_builder::{PassBuilder, TypeEquals},
+    pass_fallback::{fallback_outputs, fill_fallback_outputs, FallbackOutput, PassError},
+    pipeline_cache::PipelineCache,
+    queue_schedule::{graphics_only_states, QueueSchedule, QueueSubmission, QueueType},
+    resource::*,
+    resource_registry::ResourceRegistry,
+    shader_cache::*,
+    temporal::TemporalResources,
+    validation::{GraphValidationError, GraphValidationErrors},
+    DynamicConstants,
 };
 
 use render_core::{
@@ -16,15 +28,17 @@ use render_core::{
 };
 
 use std::{
-    collections::HashMap,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
     marker::PhantomData,
     path::{Path, PathBuf},
     sync::{Arc, Mutex, RwLock},
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum GraphResourceDesc {
     Texture(TextureDesc),
+    Buffer(BufferDesc),
 }
 
 impl From<TextureDesc> for GraphResourceDesc {
@@ -33,14 +47,191 @@ impl From<TextureDesc> for GraphResourceDesc {
     }
 }
 
+impl From<BufferDesc> for GraphResourceDesc {
+    fn from(desc: BufferDesc) -> Self {
+        Self::Buffer(desc)
+    }
+}
+
+impl GraphResourceDesc {
+    pub fn size_bytes(&self) -> usize {
+        match self {
+            Self::Texture(desc) => desc.size_bytes(),
+            Self::Buffer(desc) => desc.size,
+        }
+    }
+
+    pub fn resource_type(&self) -> RenderResourceType {
+        match self {
+            Self::Texture(..) => RenderResourceType::Texture,
+            Self::Buffer(..) => RenderResourceType::Buffer,
+        }
+    }
+}
+
+/// Bind flags a resource needs in order to be used in the given state.
+pub(crate) fn bind_flags_for_state(state: RenderResourceStates) -> RenderBindFlags {
+    let mut flags = RenderBindFlags::NONE;
+
+    if state.intersects(
+        RenderResourceStates::PIXEL_SHADER_RESOURCE
+            | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+    ) {
+        flags |= RenderBindFlags::SHADER_RESOURCE;
+    }
+    if state.contains(RenderResourceStates::UNORDERED_ACCESS) {
+        flags |= RenderBindFlags::UNORDERED_ACCESS;
+    }
+    if state.contains(RenderResourceStates::RENDER_TARGET) {
+        flags |= RenderBindFlags::RENDER_TARGET;
+    }
+    if state.intersects(RenderResourceStates::DEPTH_WRITE | RenderResourceStates::DEPTH_READ) {
+        flags |= RenderBindFlags::DEPTH_STENCIL;
+    }
+
+    flags
+}
+
+pub(crate) fn create_gpu_resource(
+    device: &dyn RenderDevice,
+    handle: RenderResourceHandle,
+    desc: &GraphResourceDesc,
+    bind_flags: RenderBindFlags,
+    name: String,
+) -> anyhow::Result<()> {
+    match desc {
+        GraphResourceDesc::Texture(desc) => {
+            // render-core textures are always single-sampled
+            anyhow::ensure!(
+                desc.sample_count == 1,
+                "{}: multisampled textures are not supported by the backend",
+                name
+            );
+
+            let texture_type = match desc.texture_type {
+                TextureType::Tex2d => RenderTextureType::Tex2d,
+                TextureType::Tex3d => RenderTextureType::Tex3d,
+                TextureType::Cube => RenderTextureType::TexCube,
+            };
+
+            device.create_texture(
+                handle,
+                &RenderTextureDesc {
+                    texture_type,
+                    bind_flags,
+                    format: desc.format,
+                    width: desc.width,
+                    height: desc.height,
+                    depth: desc.depth,
+                    levels: desc.mip_levels,
+                    elements: desc.array_elements,
+                },
+                None,
+                name.into(),
+            )?
+        }
+        GraphResourceDesc::Buffer(desc) => device.create_buffer(
+            handle,
+            &RenderBufferDesc {
+                bind_flags: bind_flags | desc.usage,
+                size: desc.size,
+            },
+            None,
+            name.into(),
+        )?,
+    }
+
+    Ok(())
+}
+
+/// Creates the persistent GPU resources backing pooled transient and temporal resources.
+/// `execute` creates them on the device, while `trace` just records that it would.
+pub(crate) trait GpuResourceCreator {
+    /// `memory_label` is what the allocation is listed as in memory reports.
+    fn create(
+        &self,
+        desc: &GraphResourceDesc,
+        bind_flags: RenderBindFlags,
+        name: &str,
+        memory_label: &str,
+    ) -> anyhow::Result<RenderResourceHandle>;
+}
+
+impl GpuResourceCreator for RenderGraphExecutionParams<'_, '_, '_> {
+    fn create(
+        &self,
+        desc: &GraphResourceDesc,
+        bind_flags: RenderBindFlags,
+        name: &str,
+        memory_label: &str,
+    ) -> anyhow::Result<RenderResourceHandle> {
+        let handle = self.handles.allocate_persistent(desc.resource_type());
+        create_gpu_resource(self.device, handle, desc, bind_flags, name.to_owned())?;
+        self.handles
+            .track_persistent_memory(handle, memory_label, desc.size_bytes());
+        Ok(handle)
+    }
+}
+
 pub(crate) struct GraphResourceCreateInfo {
+    pub name: String,
     pub desc: GraphResourceDesc,
     pub create_pass_idx: usize,
 }
 
+pub(crate) struct GraphResourceImportInfo {
+    pub resource: RenderResourceHandle,
+    pub desc: GraphResourceDesc,
+    pub state: RenderResourceStates,
+}
+
+pub(crate) struct GraphResourceTemporalInfo {
+    pub name: String,
+    pub desc: GraphResourceDesc,
+    pub instance: usize,
+    pub instance_count: usize,
+}
+
+pub(crate) enum GraphResourceInfo {
+    Created(GraphResourceCreateInfo),
+    Imported(GraphResourceImportInfo),
+    Temporal(GraphResourceTemporalInfo),
+}
+
+impl GraphResourceInfo {
+    pub fn desc(&self) -> &GraphResourceDesc {
+        match self {
+            Self::Created(info) => &info.desc,
+            Self::Imported(info) => &info.desc,
+            Self::Temporal(info) => &info.desc,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Created(info) => &info.name,
+            Self::Imported(..) => "imported",
+            Self::Temporal(info) => &info.name,
+        }
+    }
+}
+
+pub(crate) struct ExportedResource {
+    pub handle: GraphRawResourceHandle,
+    pub final_state: RenderResourceStates,
+}
+
 pub struct RenderGraph {
-    passes: Vec<RecordedPass>,
-    resources: Vec<GraphResourceCreateInfo>,
+    pub(crate) passes: Vec<RecordedPass>,
+    pub(crate) resources: Vec<GraphResourceInfo>,
+    pub(crate) exported_resources: Vec<ExportedResource>,
+    pub(crate) recording_threads: usize,
+    // Misuse detected while building the graph; reported by `validate`
+    pub(crate) build_errors: Vec<GraphValidationError>,
+    // Prefix of pass and resource names; see `scope`
+    pub(crate) name_scope: String,
+    pub(crate) gpu_timestamps: bool,
+    debug_dump_barriers: bool,
 }
 
 impl RenderGraph {
@@ -48,13 +239,41 @@ impl RenderGraph {
         Self {
             passes: Vec::new(),
             resources: Vec::new(),
+            exported_resources: Vec::new(),
+            recording_threads: 1,
+            build_errors: Vec::new(),
+            name_scope: String::new(),
+            gpu_timestamps: false,
+            debug_dump_barriers: false,
         }
     }
 
+    /// Record passes on multiple threads during `execute`. The passes of each queue submission
+    /// are split into up to `thread_count` groups of consecutive passes, each recorded
+    /// into its own command list on its own thread.
+    pub fn parallel_recording(&mut self, thread_count: usize) {
+        self.recording_threads = thread_count.max(1);
+    }
+
+    /// Write GPU timestamps around each pass during `execute`. The queries are returned
+    /// in `RenderGraphExecutionOutput::timestamp_queries`, for a `GpuProfiler` to read back.
+    pub fn gpu_timestamps(&mut self, enable: bool) {
+        self.gpu_timestamps = enable;
+    }
+
+    /// Print the barriers emitted before each pass during `execute`.
+    pub fn debug_dump_barriers(&mut self, enable: bool) {
+        self.debug_dump_barriers = enable;
+    }
+
     pub(crate) fn create_raw_resource(
         &mut self,
         info: GraphResourceCreateInfo,
     ) -> GraphRawResourceHandle {
+        self.push_raw_resource(GraphResourceInfo::Created(info))
+    }
+
+    fn push_raw_resource(&mut self, info: GraphResourceInfo) -> GraphRawResourceHandle {
         let res = GraphRawResourceHandle {
             id: self.resources.len() as u32,
             version: 0,
@@ -63,17 +282,133 @@ impl RenderGraph {
         self.resources.push(info);
         res
     }
+
+    /// Brings a resource owned outside of the graph into it. `current_state` is the state
+    /// the resource is in at the start of graph execution.
+    pub fn import<Desc: ResourceDesc>(
+        &mut self,
+        resource: RenderResourceHandle,
+        desc: Desc,
+        current_state: RenderResourceStates,
+    ) -> Handle<<Desc as ResourceDesc>::Resource>
+    where
+        Desc: TypeEquals<Other = <<Desc as ResourceDesc>::Resource as Resource>::Desc>,
+    {
+        Handle {
+            raw: self.push_raw_resource(GraphResourceInfo::Imported(GraphResourceImportInfo {
+                resource,
+                desc: desc.clone().into(),
+                state: current_state,
+            })),
+            desc: TypeEquals::same(desc),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a resource which persists across graph executions, e.g. for accumulation
+    /// or auto exposure. The resource is identified by `name` (prefixed by the current `scope`),
+    /// and recreated if `desc` changes.
+    /// Passes writing to temporal resources are never culled.
+    pub fn get_or_create_temporal<Desc: ResourceDesc>(
+        &mut self,
+        name: &str,
+        desc: Desc,
+    ) -> Handle<<Desc as ResourceDesc>::Resource>
+    where
+        Desc: TypeEquals<Other = <<Desc as ResourceDesc>::Resource as Resource>::Desc>,
+    {
+        self.temporal_handle(name, desc, 0, 1)
+    }
+
+    /// Like `get_or_create_temporal`, but backed by two resources which swap roles every frame.
+    /// Returns `(output, history)`, where `history` holds what was written to `output`
+    /// during the previous execution of a graph.
+    pub fn get_or_create_temporal_ping_pong<Desc: ResourceDesc>(
+        &mut self,
+        name: &str,
+        desc: Desc,
+    ) -> (
+        Handle<<Desc as ResourceDesc>::Resource>,
+        Handle<<Desc as ResourceDesc>::Resource>,
+    )
+    where
+        Desc: TypeEquals<Other = <<Desc as ResourceDesc>::Resource as Resource>::Desc>,
+    {
+        (
+            self.temporal_handle(name, desc.clone(), 0, 2),
+            self.temporal_handle(name, desc, 1, 2),
+        )
+    }
+
+    fn temporal_handle<Desc: ResourceDesc>(
+        &mut self,
+        name: &str,
+        desc: Desc,
+        instance: usize,
+        instance_count: usize,
+    ) -> Handle<<Desc as ResourceDesc>::Resource>
+    where
+        Desc: TypeEquals<Other = <<Desc as ResourceDesc>::Resource as Resource>::Desc>,
+    {
+        let name = self.scoped_name(name);
+        let already_used = self.resources.iter().any(|resource| match resource {
+            GraphResourceInfo::Temporal(info) => info.name == name && info.instance == instance,
+            _ => false,
+        });
+
+        if already_used {
+            self.build_errors
+                .push(GraphValidationError::DuplicateTemporal {
+                    resource: name.clone(),
+                    instance,
+                });
+        }
+
+        Handle {
+            raw: self.push_raw_resource(GraphResourceInfo::Temporal(GraphResourceTemporalInfo {
+                name,
+                desc: desc.clone().into(),
+                instance,
+                instance_count,
+            })),
+            desc: TypeEquals::same(desc),
+            marker: PhantomData,
+        }
+    }
+
+    /// Marks the resource as an output of the graph. Passes contributing to exported resources
+    /// are kept alive, and the resource is transitioned to `final_state` once all passes are done.
+    /// The GPU resource can be retrieved via `RenderGraphExecutionOutput::exported_resource`.
+    pub fn export<Res: Resource>(
+        &mut self,
+        resource: Handle<Res>,
+        final_state: RenderResourceStates,
+    ) -> ExportedHandle<Res> {
+        self.exported_resources.push(ExportedResource {
+            handle: resource.raw,
+            final_state,
+        });
+
+        ExportedHandle {
+            raw: resource.raw,
+            marker: PhantomData,
+        }
+    }
 }
 
-pub trait ResourceHandleAllocator {
+pub trait ResourceHandleAllocator: Send + Sync {
     fn allocate_transient(&self, kind: RenderResourceType) -> RenderResourceHandle;
     fn allocate_persistent(&self, kind: RenderResourceType) -> RenderResourceHandle;
+
+    /// Records the size of a persistent resource for memory reports.
+    fn track_persistent_memory(&self, _handle: RenderResourceHandle, _name: &str, _bytes: usize) {}
 }
 
 #[derive(Default)]
 pub struct TrackedResourceHandles {
     pub transient: Vec<RenderResourceHandle>,
     pub persistent: Vec<RenderResourceHandle>,
+    pub persistent_memory: Vec<(RenderResourceHandle, PersistentAllocation)>,
 }
 
 pub struct TrackingResourceHandleAllocator {
@@ -106,12 +441,115 @@ impl ResourceHandleAllocator for TrackingResourceHandleAllocator {
         self.tracked.write().unwrap().persistent.push(handle);
         handle
     }
+
+    fn track_persistent_memory(&self, handle: RenderResourceHandle, name: &str, bytes: usize) {
+        self.tracked.write().unwrap().persistent_memory.push((
+            handle,
+            PersistentAllocation {
+                name: name.to_owned(),
+                bytes,
+            },
+        ));
+    }
 }
 
-#[derive(Debug)]
-struct ResourceLifetime {
-    first_access: usize,
-    last_access: usize,
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResourceLifetime {
+    pub first_access: usize,
+    pub last_access: usize,
+}
+
+impl ResourceLifetime {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.first_access <= other.last_access && other.first_access <= self.last_access
+    }
+}
+
+/// Summary of how transient graph resources were mapped onto GPU resources in one frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceAliasingReport {
+    /// Number of transient resources created by passes in the graph
+    pub requested_resources: usize,
+    /// Number of GPU resources actually allocated to back them
+    pub allocated_resources: usize,
+    /// Memory that would have been used without aliasing
+    pub requested_bytes: usize,
+    /// Memory used after aliasing
+    pub allocated_bytes: usize,
+}
+
+impl ResourceAliasingReport {
+    pub fn bytes_saved(&self) -> usize {
+        self.requested_bytes - self.allocated_bytes
+    }
+}
+
+// A GPU resource backing one or more graph resources with disjoint lifetimes.
+pub(crate) struct AliasedResource {
+    // Names of all the graph resources sharing this GPU resource
+    pub name: String,
+    pub desc: GraphResourceDesc,
+    pub bind_flags: RenderBindFlags,
+    pub lifetime: ResourceLifetime,
+}
+
+// How graph resources are backed by GPU resources in one execution of the graph.
+pub(crate) struct ResourcePlan {
+    pub pass_live: Vec<bool>,
+    pub resource_lifetimes: Vec<Option<ResourceLifetime>>,
+    pub bind_flags: Vec<RenderBindFlags>,
+    pub resource_to_aliased: Vec<Option<usize>>,
+    pub aliased_resources: Vec<AliasedResource>,
+}
+
+// Everything the resource plan and queue schedule depend on. GPU handles and states
+// of imported resources only matter for barriers, which are calculated anew every frame.
+#[derive(PartialEq, Eq, Hash)]
+struct GraphTopology {
+    resources: Vec<TopologyResource>,
+    passes: Vec<TopologyPass>,
+    exported: Vec<GraphRawResourceHandle>,
+    recording_threads: usize,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum TopologyResource {
+    Created {
+        name: String,
+        desc: GraphResourceDesc,
+        create_pass_idx: usize,
+    },
+    Imported {
+        desc: GraphResourceDesc,
+    },
+    Temporal {
+        name: String,
+        desc: GraphResourceDesc,
+        instance: usize,
+    },
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct TopologyPass {
+    queue: QueueType,
+    never_cull: bool,
+    read: Vec<TopologyAccess>,
+    write: Vec<TopologyAccess>,
+}
+
+type TopologyAccess = (
+    GraphRawResourceHandle,
+    SubresourceRange,
+    RenderResourceStates,
+);
+
+/// Everything about the execution of a graph which only depends on its topology,
+/// and can be reused by later graphs with the same one.
+pub(crate) struct CompiledGraph {
+    topology_hash: u64,
+    topology: GraphTopology,
+    pub plan: ResourcePlan,
+    pub schedule: QueueSchedule,
 }
 
 pub struct RenderGraphExecutionParams<'device, 'pipeline_cache, 'res_alloc> {
@@ -122,119 +560,858 @@ pub struct RenderGraphExecutionParams<'device, 'pipeline_cache, 'res_alloc> {
 
 #[derive(Default)]
 pub struct RenderGraphExecutionOutput {
-    pub output_texture: RenderResourceHandle,
+    exported_resources: Vec<(GraphRawResourceHandle, RenderResourceHandle)>,
+    pub aliasing: ResourceAliasingReport,
+    pub memory: TransientMemoryReport,
+    pub submissions: Vec<QueueSubmission>,
+    /// Present if `RenderGraph::gpu_timestamps` was enabled
+    pub timestamp_queries: Option<GpuTimestampQueries>,
+    /// Passes whose render functions failed. The rest of the graph was still recorded,
+    /// with the outputs of these passes filled with a diagnostic pattern.
+    pub pass_errors: Vec<PassError>,
+}
+
+impl RenderGraphExecutionOutput {
+    pub fn exported_resource<Res: Resource>(
+        &self,
+        handle: ExportedHandle<Res>,
+    ) -> RenderResourceHandle {
+        self.exported_resources
+            .iter()
+            .find(|(raw, _)| raw.id == handle.raw.id)
+            .map(|(_, resource)| *resource)
+            .expect("exported resource")
+    }
 }
 
 impl RenderGraph {
-    pub fn add_pass<'s>(&'s mut self) -> PassBuilder<'s> {
+    pub fn add_pass<'s>(&'s mut self, name: &str) -> PassBuilder<'s> {
         let pass_idx = self.passes.len();
 
         PassBuilder {
             rg: self,
             pass_idx,
-            pass: Some(Default::default()),
+            pass: Some(RecordedPass {
+                name: self.scoped_name(name),
+                ..Default::default()
+            }),
         }
     }
 
-    fn calculate_resource_lifetimes(&self) -> Vec<ResourceLifetime> {
-        let mut resource_lifetimes: Vec<ResourceLifetime> = self
+    /// Adds a compute pass which runs on the async compute queue, overlapping with
+    /// graphics passes it doesn't share resources with.
+    pub fn add_async_compute_pass<'s>(&'s mut self, name: &str) -> PassBuilder<'s> {
+        let mut pass = self.add_pass(name);
+        pass.pass.as_mut().unwrap().queue = QueueType::AsyncCompute;
+        pass
+    }
+
+    /// Command lists `execute` will record, and how they need to be submitted.
+    pub fn queue_submissions(&self, cache: &mut RenderGraphCache) -> Vec<QueueSubmission> {
+        self.compile(cache).schedule.submissions.clone()
+    }
+
+    fn topology(&self) -> GraphTopology {
+        let resources = self
             .resources
             .iter()
-            .map(|res| ResourceLifetime {
-                first_access: res.create_pass_idx,
-                last_access: res.create_pass_idx,
+            .map(|resource| match resource {
+                GraphResourceInfo::Created(info) => TopologyResource::Created {
+                    name: info.name.clone(),
+                    desc: info.desc,
+                    create_pass_idx: info.create_pass_idx,
+                },
+                GraphResourceInfo::Imported(info) => TopologyResource::Imported { desc: info.desc },
+                GraphResourceInfo::Temporal(info) => TopologyResource::Temporal {
+                    name: info.name.clone(),
+                    desc: info.desc,
+                    instance: info.instance,
+                },
             })
             .collect();
 
+        let accesses = |resource_refs: &[PassResourceRef]| {
+            resource_refs
+                .iter()
+                .map(|resource_ref| {
+                    (
+                        resource_ref.handle,
+                        resource_ref.subresources,
+                        resource_ref.access_mode,
+                    )
+                })
+                .collect()
+        };
+
+        let passes = self
+            .passes
+            .iter()
+            .map(|pass| TopologyPass {
+                queue: pass.queue,
+                never_cull: pass.never_cull,
+                read: accesses(&pass.read),
+                write: accesses(&pass.write),
+            })
+            .collect();
+
+        GraphTopology {
+            resources,
+            passes,
+            exported: self
+                .exported_resources
+                .iter()
+                .map(|exported| exported.handle)
+                .collect(),
+            recording_threads: self.recording_threads,
+        }
+    }
+
+    /// Plans resources and schedules passes, or reuses the result for the previous graph
+    /// if it had the same topology.
+    fn compile(&self, cache: &mut RenderGraphCache) -> Arc<CompiledGraph> {
+        let topology = self.topology();
+        let topology_hash = {
+            let mut hasher = DefaultHasher::new();
+            topology.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // The hash rejects most changed graphs without comparing the whole topology.
+        if let Some(compiled) = &cache.compiled {
+            if compiled.topology_hash == topology_hash && compiled.topology == topology {
+                return compiled.clone();
+            }
+        }
+
+        let plan = self.plan_resources();
+        let schedule = self.schedule_queues(&plan.pass_live, &plan.resource_to_aliased);
+        let compiled = Arc::new(CompiledGraph {
+            topology_hash,
+            topology,
+            plan,
+            schedule,
+        });
+
+        cache.compiled = Some(compiled.clone());
+        compiled
+    }
+
+    /// Resources which are used after the graph finishes executing: exported,
+    /// and temporal ones. Passes not contributing to those can be culled.
+    pub(crate) fn culling_roots(&self) -> Vec<GraphRawResourceHandle> {
+        let temporal = self
+            .resources
+            .iter()
+            .enumerate()
+            .filter(|(_, resource)| matches!(resource, GraphResourceInfo::Temporal(..)))
+            .map(|(id, _)| GraphRawResourceHandle {
+                id: id as u32,
+                version: 0,
+            });
+
+        self.exported_resources
+            .iter()
+            .map(|exported| exported.handle)
+            .chain(temporal)
+            .collect()
+    }
+
+    /// Walks the graph backwards from the `roots`, and marks passes which contribute to them.
+    /// A pass is live if it writes to a resource needed by a later live pass, or if it opted
+    /// out of culling. All resources accessed by a live pass become needed in turn.
+    pub(crate) fn find_live_passes(&self, roots: &[GraphRawResourceHandle]) -> Vec<bool> {
+        let mut resource_needed = vec![false; self.resources.len()];
+        for root in roots {
+            resource_needed[root.id as usize] = true;
+        }
+
+        let mut pass_live = vec![false; self.passes.len()];
+
+        for (pass_idx, pass) in self.passes.iter().enumerate().rev() {
+            let contributes = pass
+                .write
+                .iter()
+                .any(|res_access| resource_needed[res_access.handle.id as usize]);
+
+            if contributes || pass.never_cull {
+                pass_live[pass_idx] = true;
+
+                for res_access in pass.read.iter().chain(pass.write.iter()) {
+                    resource_needed[res_access.handle.id as usize] = true;
+                }
+            }
+        }
+
+        pass_live
+    }
+
+    /// Calculates the span of live passes accessing each resource.
+    /// Resources not accessed by any live pass have no lifetime, and don't need to be created.
+    pub(crate) fn calculate_resource_lifetimes(
+        &self,
+        pass_live: &[bool],
+    ) -> Vec<Option<ResourceLifetime>> {
+        let mut resource_lifetimes: Vec<Option<ResourceLifetime>> =
+            (0..self.resources.len()).map(|_| None).collect();
+
         for (pass_idx, pass) in self.passes.iter().enumerate() {
+            if !pass_live[pass_idx] {
+                continue;
+            }
+
             for res_access in pass.read.iter().chain(pass.write.iter()) {
                 let res = &mut resource_lifetimes[res_access.handle.id as usize];
-                res.last_access = res.last_access.max(pass_idx);
+                match res {
+                    Some(res) => res.last_access = res.last_access.max(pass_idx),
+                    None => {
+                        let first_access = match &self.resources[res_access.handle.id as usize] {
+                            GraphResourceInfo::Created(info) => info.create_pass_idx,
+                            GraphResourceInfo::Imported(..) | GraphResourceInfo::Temporal(..) => {
+                                pass_idx
+                            }
+                        };
+
+                        *res = Some(ResourceLifetime {
+                            first_access,
+                            last_access: pass_idx,
+                        })
+                    }
+                }
             }
         }
 
         resource_lifetimes
     }
 
-    pub fn execute<'device, 'pipeline_cache, 'cb, 'commands, 'res_alloc>(
-        self,
-        params: RenderGraphExecutionParams<'device, 'pipeline_cache, 'res_alloc>,
-        dynamic_constants: &mut DynamicConstants,
-        cb: &'cb mut RenderCommandList<'commands>,
-        // TODO: use exported/imported resources instead
-        get_output_texture: Handle<Texture>,
-    ) -> anyhow::Result<RenderGraphExecutionOutput> {
-        let _resource_lifetimes = self.calculate_resource_lifetimes();
-        // TODO: alias resources
+    /// Works out bind flags for each resource from how live passes access it,
+    /// and how it's used once the graph is done.
+    fn calculate_bind_flags(&self, pass_live: &[bool]) -> Vec<RenderBindFlags> {
+        let mut bind_flags = vec![RenderBindFlags::NONE; self.resources.len()];
+
+        for (pass, _) in self
+            .passes
+            .iter()
+            .zip(pass_live.iter())
+            .filter(|(_, live)| **live)
+        {
+            for res_access in pass.read.iter().chain(pass.write.iter()) {
+                bind_flags[res_access.handle.id as usize] |=
+                    bind_flags_for_state(res_access.access_mode);
+            }
+        }
+
+        // Exported resources get transitioned into their final states, even if no pass uses them.
+        for exported in self.exported_resources.iter() {
+            bind_flags[exported.handle.id as usize] |= bind_flags_for_state(exported.final_state);
+        }
+
+        // Temporal resources are read by the next frame's graph, which isn't known yet.
+        for (resource, bind_flags) in self.resources.iter().zip(bind_flags.iter_mut()) {
+            if let GraphResourceInfo::Temporal(..) = resource {
+                *bind_flags |= RenderBindFlags::SHADER_RESOURCE;
+            }
+        }
+
+        bind_flags
+    }
+
+    /// Greedily assigns graph resources to GPU resources. Two graph resources may share
+    /// a GPU resource if their descs and bind flags are identical, and their lifetimes
+    /// don't overlap.
+    ///
+    /// Returns the index of the aliased resource for each graph resource,
+    /// or `None` if the resource is not used at all, or is imported.
+    fn alias_resources(
+        &self,
+        resource_lifetimes: &[Option<ResourceLifetime>],
+        bind_flags: &[RenderBindFlags],
+    ) -> (Vec<Option<usize>>, Vec<AliasedResource>) {
+        let mut resources_by_first_access: Vec<(usize, ResourceLifetime)> = resource_lifetimes
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| matches!(self.resources[*idx], GraphResourceInfo::Created(..)))
+            .filter_map(|(idx, lifetime)| lifetime.map(|lifetime| (idx, lifetime)))
+            .collect();
+        resources_by_first_access.sort_by_key(|(_, lifetime)| lifetime.first_access);
+
+        let mut aliased_resources: Vec<AliasedResource> = Vec::new();
+        let mut resource_to_aliased = vec![None; self.resources.len()];
+
+        for (resource_idx, lifetime) in resources_by_first_access {
+            let desc = *self.resources[resource_idx].desc();
+            let name = self.resources[resource_idx].name();
+            let bind_flags = bind_flags[resource_idx];
+
+            let compatible = aliased_resources.iter().position(|aliased| {
+                aliased.desc == desc
+                    && aliased.bind_flags == bind_flags
+                    && !aliased.lifetime.overlaps(&lifetime)
+            });
+
+            resource_to_aliased[resource_idx] = Some(if let Some(aliased_idx) = compatible {
+                let aliased = &mut aliased_resources[aliased_idx];
+                aliased.lifetime.last_access = lifetime.last_access;
+                aliased.name += ", ";
+                aliased.name += name;
+                aliased_idx
+            } else {
+                aliased_resources.push(AliasedResource {
+                    name: name.to_owned(),
+                    desc,
+                    bind_flags,
+                    lifetime,
+                });
+                aliased_resources.len() - 1
+            });
+        }
+
+        (resource_to_aliased, aliased_resources)
+    }
+
+    pub(crate) fn plan_resources(&self) -> ResourcePlan {
+        let pass_live = self.find_live_passes(&self.culling_roots());
+        let mut resource_lifetimes = self.calculate_resource_lifetimes(&pass_live);
+
+        // Exported resources are used after the graph finishes, so nothing may alias them.
+        for exported in self.exported_resources.iter() {
+            resource_lifetimes[exported.handle.id as usize]
+                .get_or_insert(ResourceLifetime {
+                    first_access: 0,
+                    last_access: 0,
+                })
+                .last_access = usize::MAX;
+        }
+
+        let bind_flags = self.calculate_bind_flags(&pass_live);
+        let (resource_to_aliased, aliased_resources) =
+            self.alias_resources(&resource_lifetimes, &bind_flags);
 
         /* println!(
             "Resources: {:#?}",
             self.resources
                 .iter()
-                .map(|info| info.desc)
+                .map(|info| *info.desc())
                 .zip(resource_lifetimes.iter())
+                .zip(resource_to_aliased.iter())
                 .collect::<Vec<_>>()
         ); */
 
-        let handles = &params.handles;
-        let device = params.device;
+        ResourcePlan {
+            pass_live,
+            resource_lifetimes,
+            bind_flags,
+            resource_to_aliased,
+            aliased_resources,
+        }
+    }
+
+    /// Validates the graph, backs its resources with GPU resources, and works out
+    /// the barriers before each live pass. Shared by `execute` and `trace`, which only
+    /// differ in what `creator` does, and what they then record.
+    pub(crate) fn prepare_execution(
+        &self,
+        creator: &dyn GpuResourceCreator,
+        cache: &mut RenderGraphCache,
+        temporal_resources: &mut TemporalResources,
+    ) -> anyhow::Result<PreparedExecution> {
+        self.validate()?;
+
+        let compiled = self.compile(cache);
+        let ResourcePlan {
+            pass_live,
+            bind_flags,
+            resource_to_aliased,
+            aliased_resources,
+            ..
+        } = &compiled.plan;
+        let schedule = &compiled.schedule;
+
+        let pooled_resources = aliased_resources
+            .iter()
+            .map(|aliased: &AliasedResource| {
+                cache.acquire(creator, aliased.desc, aliased.bind_flags, &aliased.name)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
+        let aliased_gpu_resources: Vec<RenderResourceHandle> = pooled_resources
+            .iter()
+            .map(|pooled| pooled.handle)
+            .collect();
+
+        // Ping-pong instances swap roles every frame, so each needs to support the usage of both.
+        let mut temporal_bind_flags: HashMap<&str, RenderBindFlags> = HashMap::new();
+        for (resource, bind_flags) in self.resources.iter().zip(bind_flags.iter()) {
+            if let GraphResourceInfo::Temporal(info) = resource {
+                *temporal_bind_flags
+                    .entry(&info.name)
+                    .or_insert(RenderBindFlags::NONE) |= *bind_flags;
+            }
+        }
+
+        let mut temporal_instances = Vec::new();
+        for resource in self.resources.iter() {
+            temporal_instances.push(match resource {
+                GraphResourceInfo::Temporal(info) => Some(
+                    temporal_resources.get_or_create(
+                        creator,
+                        &info.name,
+                        info.desc,
+                        temporal_bind_flags[info.name.as_str()],
+                        info.instance_count,
+                    )?[info.instance],
+                ),
+                _ => None,
+            });
+        }
+
+        // Created resources without a backing GPU resource are never accessed by live passes.
         let gpu_resources: Vec<RenderResourceHandle> = self
             .resources
             .iter()
-            .map(|resource: &GraphResourceCreateInfo| match resource.desc {
-                GraphResourceDesc::Texture(desc) => {
-                    let handle = handles.allocate_transient(RenderResourceType::Texture);
-                    device
-                        .create_texture(
-                            handle,
-                            &RenderTextureDesc {
-                                texture_type: RenderTextureType::Tex2d,
-                                bind_flags: RenderBindFlags::UNORDERED_ACCESS
-                                    | RenderBindFlags::SHADER_RESOURCE
-                                    | RenderBindFlags::RENDER_TARGET,
-                                format: desc.format,
-                                width: desc.width,
-                                height: desc.height,
-                                depth: 1,
-                                levels: 1,
-                                elements: 1,
-                            },
-                            None,
-                            "rg texture".into(),
-                        )
-                        .unwrap();
-
-                    handle
+            .zip(resource_to_aliased.iter())
+            .zip(temporal_instances.iter())
+            .map(|((resource, aliased_idx), temporal)| match resource {
+                GraphResourceInfo::Imported(info) => info.resource,
+                GraphResourceInfo::Temporal(..) => temporal.unwrap().handle,
+                GraphResourceInfo::Created(..) => aliased_idx
+                    .map(|aliased_idx| aliased_gpu_resources[aliased_idx])
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let mut state_tracker = ResourceStateTracker::default();
+
+        for (resource, gpu_resource) in self.resources.iter().zip(gpu_resources.iter()) {
+            if let GraphResourceInfo::Imported(info) = resource {
+                state_tracker.set_state(*gpu_resource, info.state);
+            }
+        }
+
+        for temporal in temporal_instances.iter().flatten() {
+            if let Some(state) = temporal.state {
+                state_tracker.set_state(temporal.handle, state);
+            }
+        }
+
+        for pooled in pooled_resources.iter() {
+            if let Some(state) = pooled.state {
+                state_tracker.set_state(pooled.handle, state);
+            }
+        }
+
+        let (mut pass_barriers, final_barriers) =
+            self.calculate_barriers(&mut state_tracker, pass_live, &gpu_resources);
+
+        // Barriers are all known up-front, so the temporal resources can be updated for the next
+        // frame before any passes run. This way they stay consistent even if a pass fails.
+        for (resource, gpu_resource) in self.resources.iter().zip(gpu_resources.iter()) {
+            if let GraphResourceInfo::Temporal(info) = resource {
+                if let Some(state) = state_tracker.state(*gpu_resource) {
+                    temporal_resources.set_state(&info.name, info.instance, state);
                 }
+            }
+        }
+
+        for resource in self.resources.iter() {
+            if let GraphResourceInfo::Temporal(info) = resource {
+                if info.instance_count > 1 && info.instance == 0 {
+                    temporal_resources.swap(&info.name);
+                }
+            }
+        }
+        temporal_resources.end_frame();
+
+        for pooled in pooled_resources.iter() {
+            if let Some(state) = state_tracker.state(pooled.handle) {
+                cache.set_state(pooled.handle, state);
+            }
+        }
+
+        if self.debug_dump_barriers {
+            for ((pass, live), barriers) in self
+                .passes
+                .iter()
+                .zip(pass_live.iter())
+                .zip(pass_barriers.iter())
+            {
+                if *live {
+                    Self::dump_barriers(&pass.name, barriers);
+                } else {
+                    println!("{}: culled", pass.name);
+                }
+            }
+            Self::dump_barriers("exports", &final_barriers);
+        }
+
+        let trailing_barriers =
+            self.distribute_barriers(schedule, &gpu_resources, &mut pass_barriers, final_barriers);
+
+        let mut command_list_passes: Vec<Vec<ScheduledPass>> = (0..schedule.command_list_count)
+            .map(|_| Vec::new())
+            .collect();
+        let mut recorded_pass_names = Vec::new();
+
+        for ((pass_idx, pass), barriers) in self
+            .passes
+            .iter()
+            .enumerate()
+            .zip(pass_barriers.into_iter())
+        {
+            if let Some(command_list) = schedule.pass_command_list[pass_idx] {
+                command_list_passes[command_list].push(ScheduledPass {
+                    pass_idx,
+                    name: pass.name.clone(),
+                    barriers,
+                    query_idx: recorded_pass_names.len() as u32 * 2,
+                    fallback_outputs: fallback_outputs(pass, &self.resources),
+                });
+                recorded_pass_names.push(pass.name.clone());
+            }
+        }
+
+        Ok(PreparedExecution {
+            compiled,
+            gpu_resources,
+            command_list_passes,
+            trailing_barriers,
+            recorded_pass_names,
+        })
+    }
+
+    /// Records the live passes into `command_lists`, which need to hold all the lists
+    /// referenced by the submissions returned by `queue_submissions`.
+    ///
+    /// Transient resources are taken from the pool in `cache`. They need to be returned
+    /// to it via `RenderGraphCache::take_frame` and `RenderGraphCache::recycle`.
+    ///
+    /// A pass whose render function fails doesn't stop execution: the error is returned
+    /// in `RenderGraphExecutionOutput::pass_errors`, and the textures the pass writes are
+    /// filled with a magenta pattern instead. Commands the pass recorded before failing
+    /// are kept, but render passes and debug events it opened via `ResourceRegistry`
+    /// are closed first, so the command list stays well-formed.
+    pub fn execute<'device, 'pipeline_cache, 'commands, 'res_alloc>(
+        self,
+        params: RenderGraphExecutionParams<'device, 'pipeline_cache, 'res_alloc>,
+        cache: &mut RenderGraphCache,
+        temporal_resources: &mut TemporalResources,
+        dynamic_constants: &DynamicConstants,
+        command_lists: &mut [RenderCommandList<'commands>],
+    ) -> anyhow::Result<RenderGraphExecutionOutput> {
+        let prepared = self.prepare_execution(&params, cache, temporal_resources)?;
+        let ResourcePlan {
+            pass_live,
+            resource_lifetimes,
+            resource_to_aliased,
+            aliased_resources,
+            ..
+        } = &prepared.compiled.plan;
+
+        let schedule = &prepared.compiled.schedule;
+        anyhow::ensure!(
+            command_lists.len() >= schedule.command_list_count,
+            "The graph needs {} command lists, but only {} were provided",
+            schedule.command_list_count,
+            command_lists.len()
+        );
+
+        let used_resources = || {
+            self.resources
+                .iter()
+                .zip(resource_to_aliased.iter())
+                .filter(|(_, aliased)| aliased.is_some())
+                .map(|(resource, _)| resource)
+        };
+
+        let aliasing = ResourceAliasingReport {
+            requested_resources: used_resources().count(),
+            allocated_resources: aliased_resources.len(),
+            requested_bytes: used_resources()
+                .map(|resource| resource.desc().size_bytes())
+                .sum(),
+            allocated_bytes: aliased_resources
+                .iter()
+                .map(|aliased| aliased.desc.size_bytes())
+                .sum(),
+        };
+
+        let mut transient_resources: Vec<TransientResourceMemory> = used_resources()
+            .map(|resource| TransientResourceMemory {
+                name: resource.name().to_owned(),
+                desc: *resource.desc(),
+                bytes: resource.desc().size_bytes(),
+            })
+            .collect();
+        transient_resources.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+        let alive_during = |lifetime: &Option<ResourceLifetime>, pass_idx: usize| {
+            lifetime.map_or(false, |lifetime| {
+                lifetime.first_access <= pass_idx && pass_idx <= lifetime.last_access
+            })
+        };
+
+        let peak_bytes = (0..self.passes.len())
+            .filter(|pass_idx| pass_live[*pass_idx])
+            .map(|pass_idx| {
+                self.resources
+                    .iter()
+                    .zip(resource_lifetimes.iter())
+                    .zip(resource_to_aliased.iter())
+                    .filter(|((_, lifetime), aliased)| {
+                        aliased.is_some() && alive_during(lifetime, pass_idx)
+                    })
+                    .map(|((resource, _), _)| resource.desc().size_bytes())
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap_or(0);
+
+        let memory = TransientMemoryReport {
+            resources: transient_resources,
+            peak_bytes,
+            allocated_bytes: aliasing.allocated_bytes,
+        };
+
+        // A fresh heap every frame, so the queries never need to be reset.
+        let timestamp_queries = if self.gpu_timestamps && !prepared.recorded_pass_names.is_empty() {
+            let heap = params
+                .handles
+                .allocate_transient(RenderResourceType::TimingHeap);
+            params.device.create_timing_heap(
+                heap,
+                &RenderTimingHeapDesc {
+                    count: prepared.recorded_pass_names.len() as u32 * 2,
+                },
+                "gpu timestamps".into(),
+            )?;
+
+            Some(GpuTimestampQueries {
+                heap,
+                passes: prepared.recorded_pass_names.clone(),
+            })
+        } else {
+            None
+        };
+        let timestamp_heap = timestamp_queries.as_ref().map(|queries| queries.heap);
+
+        let exported_resources = self
+            .exported_resources
+            .iter()
+            .map(|exported| {
+                (
+                    exported.handle,
+                    prepared.gpu_resources[exported.handle.id as usize],
+                )
+            })
+            .collect();
+
+        // Render functions in the order their passes are recorded in each command list
+        let mut render_fns: Vec<Option<Box<DynRenderFn>>> =
+            self.passes.into_iter().map(|pass| pass.render_fn).collect();
+        let command_list_render_fns: Vec<Vec<Box<DynRenderFn>>> = prepared
+            .command_list_passes
+            .iter()
+            .map(|passes| {
+                passes
+                    .iter()
+                    .map(|pass| render_fns[pass.pass_idx].take().unwrap())
+                    .collect()
             })
             .collect();
 
-        let mut resource_registry = ResourceRegistry {
-            execution_params: &params,
-            resources: gpu_resources,
-            dynamic_constants: dynamic_constants,
+        let record_command_list = |cb: &mut RenderCommandList<'commands>,
+                                   passes: &[ScheduledPass],
+                                   render_fns: Vec<Box<DynRenderFn>>,
+                                   trailing_barriers: &[ResourceBarrier]|
+         -> anyhow::Result<Vec<PassError>> {
+            let mut recorder = CommandListRecorder {
+                cb,
+                registry: ResourceRegistry {
+                    execution_params: &params,
+                    resources: prepared.gpu_resources.clone(),
+                    dynamic_constants,
+                    current_pass_name: String::new(),
+                    open_render_pass: false,
+                    open_events: 0,
+                },
+                render_fns: render_fns.into_iter(),
+                timestamp_heap,
+                transitions: Vec::new(),
+            };
+
+            record_passes(
+                &mut recorder,
+                passes,
+                trailing_barriers,
+                timestamp_heap.is_some(),
+            )
         };
 
-        let mut transitions = Vec::new();
+        let mut pass_errors = Vec::new();
+        let work = command_lists
+            .iter_mut()
+            .zip(prepared.command_list_passes.iter())
+            .zip(command_list_render_fns.into_iter())
+            .zip(prepared.trailing_barriers.iter());
+
+        if self.recording_threads > 1 {
+            let record_command_list = &record_command_list;
+            let results: Vec<anyhow::Result<Vec<PassError>>> =
+                crossbeam_utils::thread::scope(|scope| {
+                    let threads: Vec<_> = work
+                        .map(|(((cb, passes), render_fns), trailing_barriers)| {
+                            scope.spawn(move |_| {
+                                record_command_list(cb, passes, render_fns, trailing_barriers)
+                            })
+                        })
+                        .collect();
+
+                    threads
+                        .into_iter()
+                        .map(|thread| thread.join().expect("command list recording panicked"))
+                        .collect()
+                })
+                .expect("command list recording panicked");
 
-        for pass in self.passes.into_iter() {
-            transitions.clear();
-            for resource_ref in pass.read.iter().chain(pass.write.iter()) {
-                transitions.push((
-                    resource_registry.resources[resource_ref.handle.id as usize],
-                    resource_ref.access_mode,
-                ));
+            for result in results {
+                pass_errors.extend(result?);
             }
-            cb.transitions(&transitions)?;
+        } else {
+            for (((cb, passes), render_fns), trailing_barriers) in work {
+                pass_errors.extend(record_command_list(
+                    cb,
+                    passes,
+                    render_fns,
+                    trailing_barriers,
+                )?);
+            }
+        }
+
+        Ok(RenderGraphExecutionOutput {
+            exported_resources,
+            aliasing,
+            memory,
+            submissions: schedule.submissions.clone(),
+            timestamp_queries,
+            pass_errors,
+        })
+    }
+
+    /// Works out the minimal set of barriers needed before each live pass,
+    /// and at the end of the graph to put exported resources in their final states.
+    pub(crate) fn calculate_barriers(
+        &self,
+        state_tracker: &mut ResourceStateTracker,
+        pass_live: &[bool],
+        gpu_resources: &[RenderResourceHandle],
+    ) -> (Vec<Vec<ResourceBarrier>>, Vec<ResourceBarrier>) {
+        let pass_barriers = self
+            .passes
+            .iter()
+            .zip(pass_live.iter())
+            .map(|(pass, live)| {
+                let mut barriers = Vec::new();
 
-            (pass.render_fn.unwrap())(cb, &mut resource_registry)?;
+                if *live {
+                    for resource_ref in pass.read.iter().chain(pass.write.iter()) {
+                        state_tracker.require(
+                            gpu_resources[resource_ref.handle.id as usize],
+                            resource_ref.subresources,
+                            resource_ref.access_mode,
+                            &mut barriers,
+                        );
+                    }
+                    state_tracker.end_batch(&barriers);
+                }
+
+                barriers
+            })
+            .collect();
+
+        let mut final_barriers = Vec::new();
+        for exported in self.exported_resources.iter() {
+            state_tracker.require(
+                gpu_resources[exported.handle.id as usize],
+                SubresourceRange::all(),
+                exported.final_state,
+                &mut final_barriers,
+            );
         }
+        state_tracker.end_batch(&final_barriers);
+
+        (pass_barriers, final_barriers)
+    }
+
+    /// Assigns barriers to command lists. Transitions the async compute queue can't make
+    /// are moved to the graphics queue, and the ones into final states of exported resources
+    /// go at the end of the graph. Returns the barriers to record at the end of each
+    /// command list, after its passes.
+    pub(crate) fn distribute_barriers(
+        &self,
+        schedule: &QueueSchedule,
+        gpu_resources: &[RenderResourceHandle],
+        pass_barriers: &mut [Vec<ResourceBarrier>],
+        final_barriers: Vec<ResourceBarrier>,
+    ) -> Vec<Vec<ResourceBarrier>> {
+        let mut trailing_barriers: Vec<Vec<ResourceBarrier>> =
+            vec![Vec::new(); schedule.command_list_count];
+        let last_command_list =
+            |submission: usize| schedule.submissions[submission].command_lists.end - 1;
 
-        let output_texture = resource_registry.resources[get_output_texture.raw.id as usize];
-        assert!(output_texture.get_type() == RenderResourceType::Texture);
+        // Latest graphics submission which accessed each GPU resource
+        let mut last_graphics_submission: HashMap<RenderResourceHandle, usize> = HashMap::new();
 
-        Ok(RenderGraphExecutionOutput { output_texture })
+        for (pass_idx, pass) in self.passes.iter().enumerate() {
+            let submission = match schedule.pass_submission[pass_idx] {
+                Some(submission) => submission,
+                None => continue,
+            };
+
+            match pass.queue {
+                QueueType::Graphics => {
+                    for resource_ref in pass.read.iter().chain(pass.write.iter()) {
+                        last_graphics_submission
+                            .insert(gpu_resources[resource_ref.handle.id as usize], submission);
+                    }
+                }
+                QueueType::AsyncCompute => {
+                    // The compute queue can't transition resources out of graphics states,
+                    // so those transitions go at the end of the graphics submission which last
+                    // used the resource instead. The compute submission waits for that one.
+                    // Resources untouched by graphics passes so far come from outside
+                    // of the graph, and are left to the compute queue.
+                    let (graphics_barriers, compute_barriers): (Vec<_>, Vec<_>) =
+                        std::mem::take(&mut pass_barriers[pass_idx])
+                            .into_iter()
+                            .partition(|barrier| {
+                                barrier.before.map_or(false, |before| {
+                                    before.intersects(graphics_only_states())
+                                }) && last_graphics_submission.contains_key(&barrier.resource)
+                            });
+
+                    for barrier in graphics_barriers {
+                        trailing_barriers
+                            [last_command_list(last_graphics_submission[&barrier.resource])]
+                        .push(barrier);
+                    }
+
+                    pass_barriers[pass_idx] = compute_barriers;
+                }
+            }
+        }
+
+        trailing_barriers[last_command_list(schedule.final_submission)].extend(final_barriers);
+
+        trailing_barriers
+    }
+
+    fn dump_barriers(label: &str, barriers: &[ResourceBarrier]) {
+        println!("{}: {} barriers", label, barriers.len());
+        for barrier in barriers {
+            println!(
+                "    {:?}: {:?} -> {:?}",
+                barrier.resource, barrier.before, barrier.after
+            );
+        }
     }
 
     pub(crate) fn record_pass(&mut self, pass: RecordedPass) {
@@ -243,16 +1420,261 @@ impl RenderGraph {
 }
 
 type DynRenderFn =
-    dyn FnOnce(&mut RenderCommandList<'_>, &mut ResourceRegistry) -> anyhow::Result<()>;
+    dyn FnOnce(&mut RenderCommandList<'_>, &mut ResourceRegistry) -> anyhow::Result<()> + Send;
+
+// A live pass ready to be recorded
+pub(crate) struct ScheduledPass {
+    pub pass_idx: usize,
+    pub name: String,
+    pub barriers: Vec<ResourceBarrier>,
+    /// Index of the timestamp query written before the pass; the one after it follows.
+    pub query_idx: u32,
+    /// Filled with the error pattern if the pass fails
+    pub fallback_outputs: Vec<FallbackOutput>,
+}
+
+// GPU resources and barriers for one execution of a graph; see `prepare_execution`.
+pub(crate) struct PreparedExecution {
+    pub compiled: Arc<CompiledGraph>,
+    pub gpu_resources: Vec<RenderResourceHandle>,
+    /// Live passes with their barriers, grouped by the command list they're recorded into
+    pub command_list_passes: Vec<Vec<ScheduledPass>>,
+    /// Barriers recorded at the end of each command list, after its passes
+    pub trailing_barriers: Vec<Vec<ResourceBarrier>>,
+    /// Names of the recorded passes, in the order of their timestamp queries
+    pub recorded_pass_names: Vec<String>,
+}
+
+/// Receives the commands of one command list. `execute` records them into
+/// a `RenderCommandList`, and `trace` turns them into trace events.
+pub(crate) trait PassRecorder {
+    fn transitions(&mut self, barriers: &[ResourceBarrier]) -> anyhow::Result<()>;
+    fn begin_pass(&mut self, pass: &ScheduledPass) -> anyhow::Result<()>;
+    fn end_pass(&mut self) -> anyhow::Result<()>;
+    fn write_timestamp(&mut self, query_idx: u32) -> anyhow::Result<()>;
+
+    /// Runs the render function of the pass. An error only fails this pass.
+    fn render(&mut self, pass: &ScheduledPass) -> anyhow::Result<()>;
+
+    fn fill_fallback_outputs(&mut self, pass: &ScheduledPass) -> anyhow::Result<()>;
+}
+
+/// Records `passes` followed by `trailing_barriers`, and returns the errors of passes
+/// which failed. Errors of the recorder itself abort recording instead.
+pub(crate) fn record_passes(
+    recorder: &mut dyn PassRecorder,
+    passes: &[ScheduledPass],
+    trailing_barriers: &[ResourceBarrier],
+    timestamps: bool,
+) -> anyhow::Result<Vec<PassError>> {
+    let mut pass_errors = Vec::new();
+
+    for pass in passes {
+        recorder.transitions(&pass.barriers)?;
+        recorder.begin_pass(pass)?;
+        if timestamps {
+            recorder.write_timestamp(pass.query_idx)?;
+        }
+
+        if let Err(error) = recorder.render(pass) {
+            // The barriers are all precomputed, so the rest of the graph doesn't
+            // depend on what the pass did; only its outputs need patching up.
+            let error = match recorder.fill_fallback_outputs(pass) {
+                Ok(()) => error,
+                Err(fill_error) => error.context(format!(
+                    "Filling the outputs with the error pattern also failed: {:?}",
+                    fill_error
+                )),
+            };
+
+            pass_errors.push(PassError {
+                pass: pass.name.clone(),
+                error,
+            });
+        }
+
+        if timestamps {
+            recorder.write_timestamp(pass.query_idx + 1)?;
+        }
+        recorder.end_pass()?;
+    }
+
+    recorder.transitions(trailing_barriers)?;
+    Ok(pass_errors)
+}
+
+struct CommandListRecorder<
+    'cb,
+    'commands,
+    'exec_params,
+    'device,
+    'pipeline_cache,
+    'res_alloc,
+    'constants,
+> {
+    cb: &'cb mut RenderCommandList<'commands>,
+    registry: ResourceRegistry<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>,
+    // Render functions of the passes, in recording order
+    render_fns: std::vec::IntoIter<Box<DynRenderFn>>,
+    timestamp_heap: Option<RenderResourceHandle>,
+    transitions: Vec<(RenderResourceHandle, RenderResourceStates)>,
+}
+
+impl PassRecorder for CommandListRecorder<'_, '_, '_, '_, '_, '_, '_> {
+    fn transitions(&mut self, barriers: &[ResourceBarrier]) -> anyhow::Result<()> {
+        if barriers.is_empty() {
+            return Ok(());
+        }
+
+        self.transitions.clear();
+        self.transitions
+            .extend(barriers.iter().map(|b| (b.resource, b.after)));
+        self.cb.transitions(&self.transitions)?;
+
+        Ok(())
+    }
+
+    fn begin_pass(&mut self, pass: &ScheduledPass) -> anyhow::Result<()> {
+        self.cb.begin_event(&pass.name)?;
+        self.registry.current_pass_name = pass.name.clone();
+        Ok(())
+    }
+
+    fn end_pass(&mut self) -> anyhow::Result<()> {
+        self.cb.end_event()?;
+        Ok(())
+    }
+
+    fn write_timestamp(&mut self, query_idx: u32) -> anyhow::Result<()> {
+        if let Some(heap) = self.timestamp_heap {
+            self.cb.write_timestamp(heap, query_idx)?;
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, _pass: &ScheduledPass) -> anyhow::Result<()> {
+        let render_fn = self.render_fns.next().unwrap();
+        let result = render_fn(self.cb, &mut self.registry);
+
+        // Whatever the pass left open needs closing before anything else is recorded,
+        // including the fallback fills, which begin render passes of their own.
+        match (result, self.registry.close_open_scopes(self.cb)) {
+            (Err(error), _) => Err(error),
+            (Ok(()), Err(close_error)) => Err(close_error),
+            (Ok(()), Ok(true)) => Err(anyhow::anyhow!(
+                "The pass left a render pass or debug event open"
+            )),
+            (Ok(()), Ok(false)) => Ok(()),
+        }
+    }
+
+    fn fill_fallback_outputs(&mut self, pass: &ScheduledPass) -> anyhow::Result<()> {
+        fill_fallback_outputs(self.cb, &self.registry, &pass.fallback_outputs)
+    }
+}
 
 pub(crate) struct PassResourceRef {
     pub handle: GraphRawResourceHandle,
+    pub subresources: SubresourceRange,
     pub access_mode: RenderResourceStates,
 }
 
 #[derive(Default)]
 pub(crate) struct RecordedPass {
+    pub name: String,
     pub read: Vec<PassResourceRef>,
     pub write: Vec<PassResourceRef>,
     pub render_fn: Option<Box<DynRenderFn>>,
+    pub never_cull: bool,
+    pub queue: QueueType,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synth_pass(rg: &mut RenderGraph, name: &str) -> Handle<Texture> {
+        let mut pass = rg.add_pass(name);
+        let mut output = pass.create(
+            name,
+            &TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float),
+        );
+        pass.write(&mut output);
+        pass.render(|_, _| Ok(()));
+        output
+    }
+
+    #[test]
+    fn bind_flags_include_export_state() {
+        let mut rg = RenderGraph::new();
+        let tex = synth_pass(&mut rg, "synth");
+        rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+        assert_eq!(
+            rg.plan_resources().bind_flags[0],
+            RenderBindFlags::UNORDERED_ACCESS | RenderBindFlags::SHADER_RESOURCE
+        );
+    }
+
+    #[test]
+    fn bind_flags_of_exports_untouched_by_passes() {
+        let mut rg = RenderGraph::new();
+        let tex = {
+            let mut pass = rg.add_pass("create");
+            let tex = pass.create(
+                "untouched",
+                &TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float),
+            );
+            pass.render(|_, _| Ok(()));
+            tex
+        };
+        rg.export(tex, RenderResourceStates::RENDER_TARGET);
+
+        let plan = rg.plan_resources();
+        assert_eq!(plan.bind_flags[0], RenderBindFlags::RENDER_TARGET);
+        assert_eq!(
+            plan.aliased_resources[0].bind_flags,
+            RenderBindFlags::RENDER_TARGET
+        );
+    }
+
+    #[test]
+    fn temporal_resources_can_be_read_next_frame() {
+        let mut rg = RenderGraph::new();
+        let mut history = rg.get_or_create_temporal(
+            "history",
+            TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float),
+        );
+
+        let mut pass = rg.add_pass("accumulate");
+        pass.write(&mut history);
+        pass.render(|_, _| Ok(()));
+
+        assert_eq!(
+            rg.plan_resources().bind_flags[0],
+            RenderBindFlags::UNORDERED_ACCESS | RenderBindFlags::SHADER_RESOURCE
+        );
+    }
+
+    #[test]
+    fn multisampled_textures_fail_validation() {
+        let mut rg = RenderGraph::new();
+        {
+            let mut pass = rg.add_pass("raster");
+            let mut msaa = pass.create(
+                "msaa",
+                &TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float).sample_count(4),
+            );
+            pass.raster(&mut msaa);
+            pass.render(|_, _| Ok(()));
+        }
+
+        let errors = rg.validate().unwrap_err();
+        let errors = &errors.downcast_ref::<GraphValidationErrors>().unwrap().0;
+        assert!(matches!(
+            errors.as_slice(),
+            [GraphValidationError::Multisampled { pass, resource, sample_count: 4 }]
+                if pass == "raster" && resource == "msaa"
+        ));
+    }
 }
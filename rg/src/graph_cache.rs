@@ -0,0 +1,136 @@
+use crate::graph::{CompiledGraph, GpuResourceCreator, GraphResourceDesc};
+use render_core::{
+    handles::RenderResourceHandle,
+    types::{RenderBindFlags, RenderResourceStates},
+};
+use std::{collections::HashMap, sync::Arc};
+
+// Number of frames a pooled resource may sit unused before it's destroyed
+const MAX_UNUSED_FRAMES: usize = 8;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+struct PoolKey {
+    desc: GraphResourceDesc,
+    bind_flags: RenderBindFlags,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct PooledResource {
+    pub handle: RenderResourceHandle,
+    /// State the resource was left in by the last frame using it;
+    /// `None` if it's just been created.
+    pub state: Option<RenderResourceStates>,
+}
+
+struct FreeResource {
+    resource: PooledResource,
+    unused_frames: usize,
+}
+
+/// Pooled resources used by one frame. Hand them back via `RenderGraphCache::recycle`
+/// once the GPU is done with the frame.
+#[must_use]
+pub struct TransientPoolFrame {
+    resources: Vec<(PoolKey, PooledResource)>,
+}
+
+/// State carried over between executions of render graphs: GPU resources backing
+/// transient graph resources, and the compiled form of the last graph.
+///
+/// Transient resources are pooled by desc and bind flags, so that graphs don't need to
+/// create and destroy them every frame. If a graph has the same topology as the previous
+/// one, its resource plan and queue schedule are reused as well.
+#[derive(Default)]
+pub struct RenderGraphCache {
+    free: HashMap<PoolKey, Vec<FreeResource>>,
+    in_use: Vec<(PoolKey, PooledResource)>,
+    evicted: Vec<RenderResourceHandle>,
+    pub(crate) compiled: Option<Arc<CompiledGraph>>,
+}
+
+impl RenderGraphCache {
+    /// Returns a GPU resource matching `desc` and `bind_flags` which isn't in use
+    /// by any frame in flight, creating one if the pool doesn't have any.
+    pub(crate) fn acquire(
+        &mut self,
+        creator: &dyn GpuResourceCreator,
+        desc: GraphResourceDesc,
+        bind_flags: RenderBindFlags,
+        name: &str,
+    ) -> anyhow::Result<PooledResource> {
+        let key = PoolKey { desc, bind_flags };
+
+        let resource = match self.free.get_mut(&key).and_then(|free| free.pop()) {
+            Some(free) => free.resource,
+            None => {
+                // Note that the GPU resource keeps this name when it's reused later.
+                let handle = creator.create(
+                    &desc,
+                    bind_flags,
+                    name,
+                    &format!("transient pool: {}", name),
+                )?;
+                PooledResource {
+                    handle,
+                    state: None,
+                }
+            }
+        };
+
+        self.in_use.push((key, resource));
+        Ok(resource)
+    }
+
+    pub(crate) fn set_state(&mut self, handle: RenderResourceHandle, state: RenderResourceStates) {
+        if let Some((_, resource)) = self
+            .in_use
+            .iter_mut()
+            .find(|(_, resource)| resource.handle == handle)
+        {
+            resource.state = Some(state);
+        }
+    }
+
+    /// Takes the pooled resources acquired since the last call.
+    pub fn take_frame(&mut self) -> TransientPoolFrame {
+        TransientPoolFrame {
+            resources: std::mem::take(&mut self.in_use),
+        }
+    }
+
+    /// Returns the resources of a frame the GPU has finished with to the pool.
+    /// Should be called once per frame; resources which haven't been needed
+    /// for a while are evicted. See `take_evicted`.
+    pub fn recycle(&mut self, frame: TransientPoolFrame) {
+        let evicted = &mut self.evicted;
+        for free in self.free.values_mut() {
+            free.retain(|free| {
+                if free.unused_frames < MAX_UNUSED_FRAMES {
+                    true
+                } else {
+                    evicted.push(free.resource.handle);
+                    false
+                }
+            });
+
+            for free in free.iter_mut() {
+                free.unused_frames += 1;
+            }
+        }
+
+        self.free.retain(|_, free| !free.is_empty());
+
+        for (key, resource) in frame.resources {
+            self.free.entry(key).or_default().push(FreeResource {
+                resource,
+                unused_frames: 0,
+            });
+        }
+    }
+
+    /// Takes the resources evicted from the pool since the last call.
+    /// They aren't used by any frame, and can be destroyed right away.
+    pub fn take_evicted(&mut self) -> Vec<RenderResourceHandle> {
+        std::mem::take(&mut self.evicted)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    graph::{GraphResourceDesc, GraphResourceInfo, PassResourceRef, RenderGraph},
+    queue_schedule::QueueType,
+    resource::{GraphRawResourceHandle, ResourceDesc, SubresourceRange},
+};
+use std::{collections::BTreeSet, fmt::Write};
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(res, "\\u{:04x}", c as u32).unwrap(),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+// Escapes user-provided names for use within a quoted DOT label.
+fn dot_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+pub(crate) fn desc_label(desc: &GraphResourceDesc) -> String {
+    match desc {
+        GraphResourceDesc::Texture(desc) => format!(
+            "{:?} {}x{}x{} {:?}, {} mips, {} elements",
+            desc.texture_type,
+            desc.width,
+            desc.height,
+            desc.depth,
+            desc.format,
+            desc.mip_levels,
+            desc.array_elements
+        ),
+        GraphResourceDesc::Buffer(desc) => {
+            format!("{} bytes, stride {}", desc.size, desc.stride)
+        }
+    }
+}
+
+fn desc_json(desc: &GraphResourceDesc) -> String {
+    match desc {
+        GraphResourceDesc::Texture(desc) => format!(
+            concat!(
+                r#"{{"type":"texture","texture_type":{},"width":{},"height":{},"depth":{},"#,
+                r#""mip_levels":{},"array_elements":{},"sample_count":{},"format":{}}}"#
+            ),
+            json_string(&format!("{:?}", desc.texture_type)),
+            desc.width,
+            desc.height,
+            desc.depth,
+            desc.mip_levels,
+            desc.array_elements,
+            desc.sample_count,
+            json_string(&format!("{:?}", desc.format))
+        ),
+        GraphResourceDesc::Buffer(desc) => format!(
+            r#"{{"type":"buffer","size":{},"stride":{},"usage":{}}}"#,
+            desc.size,
+            desc.stride,
+            json_string(&format!("{:?}", desc.usage))
+        ),
+    }
+}
+
+// Empty if the access covers the whole resource.
+fn subresources_label(subresources: SubresourceRange, desc: &GraphResourceDesc) -> String {
+    let counts = match desc {
+        GraphResourceDesc::Texture(desc) => desc.subresource_counts(),
+        GraphResourceDesc::Buffer(desc) => desc.subresource_counts(),
+    };
+
+    if subresources == SubresourceRange::all().resolve(counts) {
+        String::new()
+    } else {
+        format!(
+            "\\nmips {}..{}, slices {}..{}",
+            subresources.base_mip,
+            subresources.base_mip + subresources.mip_count,
+            subresources.base_slice,
+            subresources.base_slice + subresources.slice_count
+        )
+    }
+}
+
+fn resource_node_id(handle: GraphRawResourceHandle) -> String {
+    format!("r{}_v{}", handle.id, handle.version)
+}
+
+/// Debug dumps of the recorded graph. These don't need a device, so they can be produced
+/// in tests, or attached to bug reports.
+impl RenderGraph {
+    /// Dumps passes, resource versions, and read/write edges in the Graphviz DOT format.
+    /// Culled passes are drawn dashed.
+    pub fn to_dot(&self) -> String {
+        let pass_live = self.find_live_passes(&self.culling_roots());
+        let resource_lifetimes = self.calculate_resource_lifetimes(&pass_live);
+
+        let mut res = String::new();
+        writeln!(res, "digraph rg {{").unwrap();
+        writeln!(res, "    rankdir=LR;").unwrap();
+
+        let mut resource_versions = BTreeSet::new();
+        for pass in self.passes.iter() {
+            for resource_ref in pass.read.iter().chain(pass.write.iter()) {
+                resource_versions.insert((resource_ref.handle.id, resource_ref.handle.version));
+            }
+        }
+
+        for (id, version) in resource_versions {
+            let resource = &self.resources[id as usize];
+            let kind = match resource {
+                GraphResourceInfo::Created(..) => "",
+                GraphResourceInfo::Imported(..) => " (imported)",
+                GraphResourceInfo::Temporal(..) => " (temporal)",
+            };
+            let lifetime = match resource_lifetimes[id as usize] {
+                Some(lifetime) => {
+                    format!(
+                        "passes {}..={}",
+                        lifetime.first_access, lifetime.last_access
+                    )
+                }
+                None => "unused".to_owned(),
+            };
+
+            writeln!(
+                res,
+                "    {} [shape=ellipse, label=\"{} v{}{}\\n{}\\n{}\"];",
+                resource_node_id(GraphRawResourceHandle { id, version }),
+                dot_escape(resource.name()),
+                version,
+                kind,
+                desc_label(resource.desc()),
+                lifetime,
+            )
+            .unwrap();
+        }
+
+        for (pass_idx, pass) in self.passes.iter().enumerate() {
+            let style = if pass_live[pass_idx] {
+                "solid"
+            } else {
+                "dashed"
+            };
+            let queue = match pass.queue {
+                QueueType::Graphics => "",
+                QueueType::AsyncCompute => "\\n(async compute)",
+            };
+            writeln!(
+                res,
+                "    p{} [shape=box, style={}, label=\"{}{}\"];",
+                pass_idx,
+                style,
+                dot_escape(&pass.name),
+                queue
+            )
+            .unwrap();
+
+            let ref_label = |resource_ref: &PassResourceRef| {
+                format!(
+                    "{:?}{}",
+                    resource_ref.access_mode,
+                    subresources_label(
+                        resource_ref.subresources,
+                        self.resources[resource_ref.handle.id as usize].desc()
+                    )
+                )
+            };
+
+            for resource_ref in pass.read.iter() {
+                writeln!(
+                    res,
+                    "    {} -> p{} [label=\"{}\"];",
+                    resource_node_id(resource_ref.handle),
+                    pass_idx,
+                    ref_label(resource_ref)
+                )
+                .unwrap();
+            }
+
+            for resource_ref in pass.write.iter() {
+                writeln!(
+                    res,
+                    "    p{} -> {} [label=\"{}\"];",
+                    pass_idx,
+                    resource_node_id(resource_ref.handle),
+                    ref_label(resource_ref)
+                )
+                .unwrap();
+            }
+        }
+
+        for exported in self.exported_resources.iter() {
+            writeln!(
+                res,
+                "    {} -> exported [label=\"{:?}\"];",
+                resource_node_id(exported.handle),
+                exported.final_state
+            )
+            .unwrap();
+        }
+
+        writeln!(res, "}}").unwrap();
+        res
+    }
+
+    /// Dumps passes, resources, and read/write edges as JSON.
+    pub fn to_json(&self) -> String {
+        let pass_live = self.find_live_passes(&self.culling_roots());
+        let resource_lifetimes = self.calculate_resource_lifetimes(&pass_live);
+
+        let resources: Vec<String> = self
+            .resources
+            .iter()
+            .enumerate()
+            .map(|(id, resource)| {
+                let kind = match resource {
+                    GraphResourceInfo::Created(..) => "created",
+                    GraphResourceInfo::Imported(..) => "imported",
+                    GraphResourceInfo::Temporal(..) => "temporal",
+                };
+                let lifetime = match resource_lifetimes[id] {
+                    Some(lifetime) => format!(
+                        r#"{{"first_access":{},"last_access":{}}}"#,
+                        lifetime.first_access, lifetime.last_access
+                    ),
+                    None => "null".to_owned(),
+                };
+                let exported = self
+                    .exported_resources
+                    .iter()
+                    .find(|exported| exported.handle.id == id as u32);
+                let final_state = match exported {
+                    Some(exported) => json_string(&format!("{:?}", exported.final_state)),
+                    None => "null".to_owned(),
+                };
+
+                format!(
+                    r#"{{"id":{},"name":{},"kind":"{}","desc":{},"lifetime":{},"exported_state":{}}}"#,
+                    id,
+                    json_string(resource.name()),
+                    kind,
+                    desc_json(resource.desc()),
+                    lifetime,
+                    final_state
+                )
+            })
+            .collect();
+
+        let passes: Vec<String> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(pass_idx, pass)| {
+                let refs_json = |refs: &[PassResourceRef]| -> String {
+                    refs.iter()
+                        .map(|resource_ref| {
+                            let subresources = resource_ref.subresources;
+                            format!(
+                                concat!(
+                                    r#"{{"resource":{},"version":{},"state":{},"#,
+                                    r#""subresources":{{"base_mip":{},"mip_count":{},"#,
+                                    r#""base_slice":{},"slice_count":{}}}}}"#
+                                ),
+                                resource_ref.handle.id,
+                                resource_ref.handle.version,
+                                json_string(&format!("{:?}", resource_ref.access_mode)),
+                                subresources.base_mip,
+                                subresources.mip_count,
+                                subresources.base_slice,
+                                subresources.slice_count
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",")
+                };
+
+                format!(
+                    r#"{{"index":{},"name":{},"live":{},"queue":"{:?}","read":[{}],"write":[{}]}}"#,
+                    pass_idx,
+                    json_string(&pass.name),
+                    pass_live[pass_idx],
+                    pass.queue,
+                    refs_json(&pass.read),
+                    refs_json(&pass.write)
+                )
+            })
+            .collect();
+
+        format!(
+            r#"{{"passes":[{}],"resources":[{}]}}"#,
+            passes.join(","),
+            resources.join(",")
+        )
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    barrier::ResourceBarrier,
+    graph::{
+        record_passes, GpuResourceCreator, GraphResourceDesc, GraphResourceInfo, PassRecorder,
+        RenderGraph, ScheduledPass,
+    },
+    graph_cache::RenderGraphCache,
+    graph_dump::desc_label,
+    queue_schedule::QueueType,
+    temporal::TemporalResources,
+};
+use render_core::{
+    handles::{RenderResourceHandle, RenderResourceHandleAllocator},
+    types::{RenderBindFlags, RenderResourceStates},
+};
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+/// One step of executing a graph, as recorded by `GraphTracer::trace`.
+/// Resources are referred to by name rather than by GPU handle, so that traces
+/// of identical graphs compare equal.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// A GPU resource backing pooled transient graph resources, or a temporal resource
+    CreateResource {
+        name: String,
+        desc: GraphResourceDesc,
+        bind_flags: RenderBindFlags,
+    },
+    CullPass {
+        name: String,
+    },
+    Submit {
+        queue: QueueType,
+        wait_for: Vec<usize>,
+    },
+    CommandList {
+        index: usize,
+    },
+    Transition {
+        resource: String,
+        before: Option<RenderResourceStates>,
+        after: RenderResourceStates,
+    },
+    Pass {
+        name: String,
+    },
+    Timestamp {
+        query: u32,
+    },
+}
+
+/// Everything `RenderGraph::execute` would do with a graph, short of running
+/// the passes' render functions. Meant for comparing against expected output in tests
+/// which can't create a GPU device.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GraphTrace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl fmt::Display for GraphTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut submission_idx = 0;
+
+        for event in self.events.iter() {
+            match event {
+                TraceEvent::CreateResource {
+                    name,
+                    desc,
+                    bind_flags,
+                } => writeln!(
+                    f,
+                    "create {:?}: {}, {:?}",
+                    name,
+                    desc_label(desc),
+                    bind_flags
+                )?,
+                TraceEvent::CullPass { name } => writeln!(f, "cull {:?}", name)?,
+                TraceEvent::Submit { queue, wait_for } => {
+                    writeln!(
+                        f,
+                        "submission {} to {:?}, waiting for {:?}",
+                        submission_idx, queue, wait_for
+                    )?;
+                    submission_idx += 1;
+                }
+                TraceEvent::CommandList { index } => writeln!(f, "  command list {}", index)?,
+                TraceEvent::Transition {
+                    resource,
+                    before,
+                    after,
+                } => writeln!(
+                    f,
+                    "    transition {:?}: {:?} -> {:?}",
+                    resource, before, after
+                )?,
+                TraceEvent::Pass { name } => writeln!(f, "    pass {:?}", name)?,
+                TraceEvent::Timestamp { query } => writeln!(f, "    timestamp {}", query)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Traces graphs the way `RenderGraph::execute` runs them, frame after frame, with its own
+/// transient resource pool and temporal resources. GPU resources are stand-ins, so nothing
+/// is created on a device.
+///
+/// Resource setup, barriers and the order of recorded commands come from the same code
+/// `execute` uses. Render functions are not called though, so commands they would record
+/// aren't part of the trace, and passes never fail.
+pub struct GraphTracer {
+    handles: RenderResourceHandleAllocator,
+    cache: RenderGraphCache,
+    temporal_resources: TemporalResources,
+}
+
+impl GraphTracer {
+    pub fn new() -> Self {
+        Self {
+            handles: RenderResourceHandleAllocator::new(),
+            cache: RenderGraphCache::default(),
+            temporal_resources: TemporalResources::default(),
+        }
+    }
+
+    /// Traces one frame. Its transient resources are returned to the pool right after,
+    /// as if the GPU had already finished with it.
+    pub fn trace(&mut self, rg: &RenderGraph) -> anyhow::Result<GraphTrace> {
+        let creator = TraceResourceCreator {
+            handles: RefCell::new(&mut self.handles),
+            events: RefCell::new(Vec::new()),
+        };
+        let prepared =
+            rg.prepare_execution(&creator, &mut self.cache, &mut self.temporal_resources)?;
+        let mut events = creator.events.into_inner();
+
+        let plan = &prepared.compiled.plan;
+        let schedule = &prepared.compiled.schedule;
+
+        // Traces refer to GPU resources by the names of the graph resources they back
+        let mut names: HashMap<RenderResourceHandle, String> = HashMap::new();
+        for (id, (resource, gpu_resource)) in rg
+            .resources
+            .iter()
+            .zip(prepared.gpu_resources.iter())
+            .enumerate()
+        {
+            let name = match resource {
+                GraphResourceInfo::Created(..) => match plan.resource_to_aliased[id] {
+                    Some(aliased_idx) => plan.aliased_resources[aliased_idx].name.clone(),
+                    None => continue,
+                },
+                GraphResourceInfo::Imported(..) => format!("imported #{}", id),
+                GraphResourceInfo::Temporal(info) => format!("{} [{}]", info.name, info.instance),
+            };
+            names.insert(*gpu_resource, name);
+        }
+
+        for (pass, live) in rg.passes.iter().zip(plan.pass_live.iter()) {
+            if !live {
+                events.push(TraceEvent::CullPass {
+                    name: pass.name.clone(),
+                });
+            }
+        }
+
+        for submission in schedule.submissions.iter() {
+            events.push(TraceEvent::Submit {
+                queue: submission.queue,
+                wait_for: submission.wait_for.clone(),
+            });
+
+            for command_list in submission.command_lists.clone() {
+                events.push(TraceEvent::CommandList {
+                    index: command_list,
+                });
+
+                let mut recorder = TraceRecorder {
+                    names: &names,
+                    events: &mut events,
+                };
+                record_passes(
+                    &mut recorder,
+                    &prepared.command_list_passes[command_list],
+                    &prepared.trailing_barriers[command_list],
+                    rg.gpu_timestamps,
+                )?;
+            }
+        }
+
+        let frame = self.cache.take_frame();
+        self.cache.recycle(frame);
+        self.cache.take_evicted();
+        self.temporal_resources.take_retired();
+
+        Ok(GraphTrace { events })
+    }
+}
+
+impl Default for GraphTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderGraph {
+    /// Traces the graph as the first frame of a fresh `GraphTracer`.
+    pub fn trace(&self) -> anyhow::Result<GraphTrace> {
+        GraphTracer::new().trace(self)
+    }
+}
+
+// Allocates stand-in handles instead of creating GPU resources
+struct TraceResourceCreator<'a> {
+    handles: RefCell<&'a mut RenderResourceHandleAllocator>,
+    events: RefCell<Vec<TraceEvent>>,
+}
+
+impl GpuResourceCreator for TraceResourceCreator<'_> {
+    fn create(
+        &self,
+        desc: &GraphResourceDesc,
+        bind_flags: RenderBindFlags,
+        name: &str,
+        _memory_label: &str,
+    ) -> anyhow::Result<RenderResourceHandle> {
+        self.events.borrow_mut().push(TraceEvent::CreateResource {
+            name: name.to_owned(),
+            desc: *desc,
+            bind_flags,
+        });
+        Ok(self.handles.borrow_mut().allocate(desc.resource_type()))
+    }
+}
+
+struct TraceRecorder<'a> {
+    names: &'a HashMap<RenderResourceHandle, String>,
+    events: &'a mut Vec<TraceEvent>,
+}
+
+impl PassRecorder for TraceRecorder<'_> {
+    fn transitions(&mut self, barriers: &[ResourceBarrier]) -> anyhow::Result<()> {
+        for barrier in barriers {
+            self.events.push(TraceEvent::Transition {
+                resource: self.names[&barrier.resource].clone(),
+                before: barrier.before,
+                after: barrier.after,
+            });
+        }
+        Ok(())
+    }
+
+    fn begin_pass(&mut self, pass: &ScheduledPass) -> anyhow::Result<()> {
+        self.events.push(TraceEvent::Pass {
+            name: pass.name.clone(),
+        });
+        Ok(())
+    }
+
+    fn end_pass(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn write_timestamp(&mut self, query_idx: u32) -> anyhow::Result<()> {
+        self.events.push(TraceEvent::Timestamp { query: query_idx });
+        Ok(())
+    }
+
+    fn render(&mut self, _pass: &ScheduledPass) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn fill_fallback_outputs(&mut self, _pass: &ScheduledPass) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
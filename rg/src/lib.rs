@@ -3,18 +3,40 @@ pub mod pipeline_cache;
 pub mod resource_view;
 pub mod shader_cache;
 
+mod barrier;
 mod dynamic_constants;
+mod feature;
+mod gpu_profiler;
 mod graph;
+mod graph_cache;
+mod graph_dump;
+mod graph_trace;
+mod memory_report;
 mod pass_builder;
+mod pass_fallback;
+mod pass_reorder;
 mod pipeline;
+mod queue_schedule;
 mod render_target;
 mod resource;
 mod resource_registry;
+mod temporal;
+mod validation;
 
 pub use dynamic_constants::*;
+pub use feature::RenderFeature;
+pub use gpu_profiler::{GpuPassTiming, GpuProfiler, GpuTimestampQueries};
 pub use graph::*;
+pub use graph_cache::{RenderGraphCache, TransientPoolFrame};
+pub use graph_trace::{GraphTrace, GraphTracer, TraceEvent};
+pub use memory_report::*;
 pub use pass_builder::PassBuilder;
+pub use pass_fallback::{PassError, PassErrors};
+pub use pass_reorder::{PassMove, PassReordering};
 pub use pipeline::*;
+pub use queue_schedule::{QueueSubmission, QueueType};
 pub use render_target::*;
 pub use resource::*;
 pub use resource_registry::ResourceRegistry;
+pub use temporal::TemporalResources;
+pub use validation::{GraphValidationError, GraphValidationErrors};
@@ -0,0 +1,74 @@
+use crate::graph::GraphResourceDesc;
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub struct TransientResourceMemory {
+    pub name: String,
+    pub desc: GraphResourceDesc,
+    pub bytes: usize,
+}
+
+/// GPU memory needed by the transient resources of one graph execution.
+#[derive(Clone, Debug, Default)]
+pub struct TransientMemoryReport {
+    /// Resources created by live passes, largest first
+    pub resources: Vec<TransientResourceMemory>,
+    /// Highest total size of transient resources in use by any single pass
+    pub peak_bytes: usize,
+    /// Size of the GPU resources backing transient resources, after aliasing
+    pub allocated_bytes: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct PersistentAllocation {
+    pub name: String,
+    pub bytes: usize,
+}
+
+/// GPU memory used by a frame: the transient resources of its graph, and everything
+/// allocated persistently, e.g. temporal resources, dynamic constants or meshes.
+///
+/// GPU resources pooled to back transient resources across frames are persistent,
+/// so they are counted in `persistent` too.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    pub transient: TransientMemoryReport,
+    /// Largest first
+    pub persistent: Vec<PersistentAllocation>,
+}
+
+impl MemoryReport {
+    pub fn persistent_bytes(&self) -> usize {
+        self.persistent.iter().map(|alloc| alloc.bytes).sum()
+    }
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Transient: {:.2} MiB peak, {:.2} MiB allocated",
+            mib(self.transient.peak_bytes),
+            mib(self.transient.allocated_bytes)
+        )?;
+        for resource in self.transient.resources.iter() {
+            write!(
+                f,
+                "\n    {:8.2} MiB  {}",
+                mib(resource.bytes),
+                resource.name
+            )?;
+        }
+
+        write!(f, "\nPersistent: {:.2} MiB", mib(self.persistent_bytes()))?;
+        for alloc in self.persistent.iter() {
+            write!(f, "\n    {:8.2} MiB  {}", mib(alloc.bytes), alloc.name)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{
     graph::{GraphResourceCreateInfo, RecordedPass, RenderGraph},
+    queue_schedule::QueueType,
     resource::*,
     resource_registry::ResourceRegistry,
+    validation::GraphValidationError,
     PassResourceRef,
 };
 
@@ -33,15 +35,50 @@ impl<T: Sized> TypeEquals for T {
 }
 
 impl<'rg> PassBuilder<'rg> {
+    fn pass_name(&self) -> String {
+        self.pass.as_ref().unwrap().name.clone()
+    }
+
+    fn resource_name<Res: Resource>(&self, handle: &Handle<Res>) -> String {
+        self.rg.resources[handle.raw.id as usize].name().to_owned()
+    }
+
+    // Out of bounds ranges are reported via `RenderGraph::validate`,
+    // and replaced with the whole resource in the meantime.
+    fn resolve_subresources<Res: Resource>(
+        &mut self,
+        handle: &Handle<Res>,
+        subresources: SubresourceRange,
+    ) -> SubresourceRange {
+        let counts = handle.desc.subresource_counts();
+
+        if subresources.is_within(counts) {
+            subresources.resolve(counts)
+        } else {
+            let error = GraphValidationError::SubresourcesOutOfBounds {
+                pass: self.pass_name(),
+                resource: self.resource_name(handle),
+                subresources,
+                mip_levels: counts.0,
+                slice_count: counts.1,
+            };
+            self.rg.build_errors.push(error);
+            SubresourceRange::all().resolve(counts)
+        }
+    }
+
     pub fn create<Desc: ResourceDesc>(
         &mut self,
+        name: &str,
         desc: &Desc,
     ) -> Handle<<Desc as ResourceDesc>::Resource>
     where
         Desc: TypeEquals<Other = <<Desc as ResourceDesc>::Resource as Resource>::Desc>,
     {
+        let name = self.rg.scoped_name(name);
         let handle: Handle<<Desc as ResourceDesc>::Resource> = Handle {
             raw: self.rg.create_raw_resource(GraphResourceCreateInfo {
+                name,
                 desc: desc.clone().into(),
                 create_pass_idx: self.pass_idx,
             }),
@@ -57,26 +94,56 @@ impl<'rg> PassBuilder<'rg> {
         handle: &mut Handle<Res>,
         access_mode: RenderResourceStates,
     ) -> Ref<Res, AccessMode> {
-        let pass = self.pass.as_mut().unwrap();
+        self.write_subresources_impl(handle, SubresourceRange::all(), access_mode)
+    }
+
+    pub fn write_subresources_impl<Res: Resource, AccessMode>(
+        &mut self,
+        handle: &mut Handle<Res>,
+        subresources: SubresourceRange,
+        access_mode: RenderResourceStates,
+    ) -> Ref<Res, AccessMode> {
+        let subresources = self.resolve_subresources(handle, subresources);
+        let pass = self.pass.as_ref().unwrap();
 
         // Don't know of a good way to use the borrow checker to verify that writes and reads
         // don't overlap, and that multiple writes don't happen to the same resource.
         // The borrow checker will at least check that resources don't alias each other,
         // but for the access in render passes, we resort to a runtime check.
-        if pass.write.iter().any(|item| item.handle == handle.raw) {
-            panic!("Trying to write twice to the same resource within one render pass");
-        } else if pass.read.iter().any(|item| item.handle == handle.raw) {
-            panic!("Trying to read and write to the same resource within one render pass");
-        }
+        // Disjoint subresources of one resource may be accessed independently.
+        let overlaps = |item: &PassResourceRef| {
+            item.handle.id == handle.raw.id && item.subresources.overlaps(&subresources)
+        };
+
+        let error = if pass.write.iter().any(overlaps) {
+            Some(GraphValidationError::DuplicateWrite {
+                pass: self.pass_name(),
+                resource: self.resource_name(handle),
+            })
+        } else if pass.read.iter().any(overlaps) {
+            Some(GraphValidationError::ReadWriteOverlap {
+                pass: self.pass_name(),
+                resource: self.resource_name(handle),
+            })
+        } else {
+            None
+        };
+        self.rg.build_errors.extend(error);
+
+        // Writes produce a new version of the resource; subsequent accesses
+        // through the handle will refer to it.
+        handle.raw = handle.raw.next_version();
 
-        pass.write.push(PassResourceRef {
+        self.pass.as_mut().unwrap().write.push(PassResourceRef {
             handle: handle.raw,
+            subresources,
             access_mode,
         });
 
         Ref {
             desc: handle.desc.clone(),
-            handle: handle.raw.next_version(),
+            handle: handle.raw,
+            subresources,
             marker: PhantomData,
         }
     }
@@ -85,34 +152,133 @@ impl<'rg> PassBuilder<'rg> {
         self.write_impl(handle, RenderResourceStates::UNORDERED_ACCESS)
     }
 
+    /// Like `write`, but only for the given mips and slices of the resource.
+    pub fn write_subresources<Res: Resource>(
+        &mut self,
+        handle: &mut Handle<Res>,
+        subresources: SubresourceRange,
+    ) -> Ref<Res, GpuUav> {
+        self.write_subresources_impl(handle, subresources, RenderResourceStates::UNORDERED_ACCESS)
+    }
+
     pub fn raster<Res: Resource>(&mut self, handle: &mut Handle<Res>) -> Ref<Res, GpuRt> {
-        self.write_impl(handle, RenderResourceStates::RENDER_TARGET)
+        self.raster_subresources(handle, SubresourceRange::all())
+    }
+
+    /// Like `raster`, but only for the given mips and slices of the resource.
+    pub fn raster_subresources<Res: Resource>(
+        &mut self,
+        handle: &mut Handle<Res>,
+        subresources: SubresourceRange,
+    ) -> Ref<Res, GpuRt> {
+        self.require_graphics_queue(handle);
+        self.write_subresources_impl(handle, subresources, RenderResourceStates::RENDER_TARGET)
+    }
+
+    /// Uses the resource as the depth-stencil attachment of a render target,
+    /// with depth and stencil writes allowed.
+    pub fn depth_stencil<Res: Resource>(&mut self, handle: &mut Handle<Res>) -> Ref<Res, GpuDsv> {
+        self.require_graphics_queue(handle);
+        self.write_impl(handle, RenderResourceStates::DEPTH_WRITE)
+    }
+
+    /// Uses the resource as a depth-stencil attachment for depth and stencil tests only.
+    /// Being a read, it can be combined with reads of the resource in shaders.
+    pub fn depth_stencil_read_only<Res: Resource>(
+        &mut self,
+        handle: &Handle<Res>,
+    ) -> Ref<Res, GpuDsvReadOnly> {
+        self.require_graphics_queue(handle);
+        self.read_subresources_impl(
+            handle,
+            SubresourceRange::all(),
+            RenderResourceStates::DEPTH_READ,
+        )
+    }
+
+    fn require_graphics_queue<Res: Resource>(&mut self, handle: &Handle<Res>) {
+        if self.pass.as_ref().unwrap().queue != QueueType::Graphics {
+            let error = GraphValidationError::RasterOnAsyncCompute {
+                pass: self.pass_name(),
+                resource: self.resource_name(handle),
+            };
+            self.rg.build_errors.push(error);
+        }
     }
 
     pub fn read<Res: Resource>(&mut self, handle: &Handle<Res>) -> Ref<Res, GpuSrv> {
-        let pass = self.pass.as_mut().unwrap();
+        self.read_subresources(handle, SubresourceRange::all())
+    }
+
+    /// Like `read`, but only for the given mips and slices of the resource.
+    pub fn read_subresources<Res: Resource>(
+        &mut self,
+        handle: &Handle<Res>,
+        subresources: SubresourceRange,
+    ) -> Ref<Res, GpuSrv> {
+        // Pixel shaders don't exist on the compute queue, nor can it transition resources
+        // into states involving them.
+        let access_mode = match self.pass.as_ref().unwrap().queue {
+            QueueType::Graphics => {
+                RenderResourceStates::PIXEL_SHADER_RESOURCE
+                    | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE
+            }
+            QueueType::AsyncCompute => RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+        };
+
+        self.read_subresources_impl(handle, subresources, access_mode)
+    }
+
+    fn read_subresources_impl<Res: Resource, AccessMode>(
+        &mut self,
+        handle: &Handle<Res>,
+        subresources: SubresourceRange,
+        access_mode: RenderResourceStates,
+    ) -> Ref<Res, AccessMode> {
+        let subresources = self.resolve_subresources(handle, subresources);
+        let pass = self.pass.as_ref().unwrap();
 
         // Runtime "borrow" check; see info in `write` above.
-        if pass.write.iter().any(|item| item.handle == handle.raw) {
-            panic!("Trying to read and write to the same resource within one render pass");
+        if pass.write.iter().any(|item| {
+            item.handle.id == handle.raw.id && item.subresources.overlaps(&subresources)
+        }) {
+            let error = GraphValidationError::ReadWriteOverlap {
+                pass: self.pass_name(),
+                resource: self.resource_name(handle),
+            };
+            self.rg.build_errors.push(error);
         }
 
-        pass.write.push(PassResourceRef {
+        self.pass.as_mut().unwrap().read.push(PassResourceRef {
             handle: handle.raw,
-            access_mode: RenderResourceStates::PIXEL_SHADER_RESOURCE
-                | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+            subresources,
+            access_mode,
         });
 
         Ref {
             desc: handle.desc.clone(),
             handle: handle.raw,
+            subresources,
             marker: PhantomData,
         }
     }
 
+    /// Keeps the pass even if none of its outputs contribute to the graph's output,
+    /// e.g. because it has side effects outside of the graph.
+    pub fn never_cull(&mut self) {
+        self.pass.as_mut().unwrap().never_cull = true;
+    }
+
+    /// Sets the function recording the pass's commands.
+    ///
+    /// If the function fails, the graph closes the render passes and debug events it left
+    /// open before recording anything else. This only works for scopes opened through
+    /// `ResourceRegistry::begin_render_pass` and `ResourceRegistry::begin_event`; ones opened
+    /// directly on the command list stay open, and break the rest of it.
     pub fn render(
         mut self,
         render: impl FnOnce(&mut RenderCommandList<'_>, &mut ResourceRegistry) -> anyhow::Result<()>
+            + Send
             + 'static,
     ) {
         let prev = self
@@ -122,6 +288,11 @@ impl<'rg> PassBuilder<'rg> {
             .render_fn
             .replace(Box::new(render));
 
-        assert!(prev.is_none());
+        if prev.is_some() {
+            let error = GraphValidationError::DuplicateRenderFn {
+                pass: self.pass_name(),
+            };
+            self.rg.build_errors.push(error);
+        }
     }
 }
@@ -0,0 +1,220 @@
+use crate::{
+    command_ext::RgRenderCommandListExtension,
+    graph::{GraphResourceDesc, GraphResourceInfo, RecordedPass},
+    resource::{TextureDesc, TextureType},
+    resource_registry::ResourceRegistry,
+};
+use render_core::{
+    constants::MAX_RENDER_TARGET_COUNT,
+    encoder::RenderCommandList,
+    handles::RenderResourceHandle,
+    state::{build, RenderBindingRenderTargetView, RenderBindingView},
+    types::*,
+};
+use std::fmt;
+
+const ERROR_PATTERN_SHADER: &str = "/assets/shaders/error_pattern.hlsl";
+const ERROR_CLEAR_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+/// A pass whose render function returned an error. The pass was skipped,
+/// and its outputs filled with a diagnostic pattern.
+pub struct PassError {
+    pub pass: String,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pass {:?} failed: {:?}", self.pass, self.error)
+    }
+}
+
+impl fmt::Debug for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug)]
+pub struct PassErrors(pub Vec<PassError>);
+
+impl fmt::Display for PassErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} render graph passes failed:", self.0.len())?;
+        for error in self.0.iter() {
+            write!(f, "\n    {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PassErrors {}
+
+/// A mip of a texture written by a pass, to be filled with the error pattern
+/// if the pass fails.
+pub(crate) struct FallbackOutput {
+    pub resource_idx: usize,
+    pub desc: TextureDesc,
+    pub mip: u32,
+    /// State the pass accesses the texture in; the barriers before the pass
+    /// have already put it in a state compatible with this one.
+    pub access_mode: RenderResourceStates,
+}
+
+/// Outputs of `pass` which can be filled with the error pattern: mips of 2D textures
+/// written via unordered access or as render targets. Anything else, e.g. buffers,
+/// or textures written by copies, is left as it is.
+pub(crate) fn fallback_outputs(
+    pass: &RecordedPass,
+    resources: &[GraphResourceInfo],
+) -> Vec<FallbackOutput> {
+    let mut outputs = Vec::new();
+
+    for resource_ref in pass.write.iter() {
+        let resource_idx = resource_ref.handle.id as usize;
+        let desc = match resources[resource_idx].desc() {
+            GraphResourceDesc::Texture(desc) if desc.texture_type == TextureType::Tex2d => *desc,
+            _ => continue,
+        };
+
+        let fillable = resource_ref.access_mode == RenderResourceStates::UNORDERED_ACCESS
+            || resource_ref.access_mode == RenderResourceStates::RENDER_TARGET;
+        if !fillable {
+            continue;
+        }
+
+        let subresources = resource_ref.subresources;
+        let mip_end = subresources
+            .base_mip
+            .saturating_add(subresources.mip_count)
+            .min(desc.mip_levels);
+
+        for mip in subresources.base_mip..mip_end {
+            outputs.push(FallbackOutput {
+                resource_idx,
+                desc,
+                mip,
+                access_mode: resource_ref.access_mode,
+            });
+        }
+    }
+
+    outputs
+}
+
+/// Fills the outputs of a failed pass with a magenta pattern, so that the failure
+/// is visible on screen without taking down the rest of the graph.
+pub(crate) fn fill_fallback_outputs(
+    cb: &mut RenderCommandList<'_>,
+    registry: &ResourceRegistry,
+    outputs: &[FallbackOutput],
+) -> anyhow::Result<()> {
+    for output in outputs {
+        let resource = registry.resources[output.resource_idx];
+
+        if output.access_mode == RenderResourceStates::UNORDERED_ACCESS {
+            fill_with_pattern(cb, registry, resource, output)?;
+        } else {
+            clear_render_target(cb, registry, resource, output)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn fill_with_pattern(
+    cb: &mut RenderCommandList<'_>,
+    registry: &ResourceRegistry,
+    resource: RenderResourceHandle,
+    output: &FallbackOutput,
+) -> anyhow::Result<()> {
+    let params = registry.execution_params;
+    let pipeline = registry.compute_pipeline(ERROR_PATTERN_SHADER)?;
+
+    let shader_views = params
+        .handles
+        .allocate_transient(RenderResourceType::ShaderViews);
+    params.device.create_shader_views(
+        shader_views,
+        &RenderShaderViewsDesc {
+            shader_resource_views: Vec::new(),
+            unordered_access_views: vec![build::texture_2d_rw(
+                resource,
+                output.desc.format,
+                output.mip,
+                0,
+            )],
+        },
+        format!("{} error pattern views", registry.current_pass_name()).into(),
+    )?;
+
+    cb.rg_dispatch_2d(
+        &pipeline,
+        output.desc.mip_dims(output.mip),
+        &[RenderShaderArgument::new(shader_views)],
+    )
+}
+
+fn clear_render_target(
+    cb: &mut RenderCommandList<'_>,
+    registry: &ResourceRegistry,
+    resource: RenderResourceHandle,
+    output: &FallbackOutput,
+) -> anyhow::Result<()> {
+    let params = registry.execution_params;
+
+    let frame_binding_set_handle = params
+        .handles
+        .allocate_transient(RenderResourceType::FrameBindingSet);
+
+    let mut render_target_views = [None; MAX_RENDER_TARGET_COUNT];
+    render_target_views[0] = Some(RenderBindingRenderTargetView {
+        base: RenderBindingView {
+            resource,
+            format: output.desc.format,
+            dimension: RenderViewDimension::Tex2d,
+        },
+        mip_slice: output.mip,
+        first_array_slice: 0,
+        plane_slice_first_w_slice: 0,
+        array_size: 0,
+        w_size: 0,
+    });
+
+    params.device.create_frame_binding_set(
+        frame_binding_set_handle,
+        &RenderFrameBindingSetDesc {
+            render_target_views,
+            depth_stencil_view: None,
+        },
+        format!("{} error clear binding set", registry.current_pass_name()).into(),
+    )?;
+
+    let render_pass_handle = params
+        .handles
+        .allocate_transient(RenderResourceType::RenderPass);
+
+    params.device.create_render_pass(
+        render_pass_handle,
+        &RenderPassDesc {
+            frame_binding: frame_binding_set_handle,
+            render_target_info: [RenderTargetInfo {
+                load_op: RenderLoadOp::Clear,
+                store_op: RenderStoreOp::Store,
+                clear_color: ERROR_CLEAR_COLOR,
+            }; MAX_RENDER_TARGET_COUNT],
+            depth_stencil_target_info: DepthStencilTargetInfo {
+                load_op: RenderLoadOp::Discard,
+                store_op: RenderStoreOp::Discard,
+                clear_depth: 1.0,
+                clear_stencil: 0,
+            },
+        },
+        format!("{} error clear", registry.current_pass_name()).into(),
+    )?;
+
+    cb.begin_render_pass(render_pass_handle)?;
+    cb.end_render_pass()?;
+
+    Ok(())
+}
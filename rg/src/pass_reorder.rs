@@ -0,0 +1,532 @@
+use crate::{
+    barrier::is_read_only,
+    graph::{GraphResourceInfo, PassResourceRef, RecordedPass, RenderGraph},
+};
+use render_core::types::RenderResourceStates;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// How `RenderGraph::reorder_passes` searches for a pass order.
+#[derive(Clone, Copy, Debug)]
+pub enum PassReordering {
+    /// Greedy scheduling only. Identical graphs always get identical schedules.
+    Deterministic,
+    /// Greedy scheduling refined by swapping adjacent passes for as long as `time_budget` allows.
+    /// Can find better schedules, but the result depends on CPU speed and load.
+    Search { time_budget: Duration },
+}
+
+/// One decision made by `RenderGraph::reorder_passes`.
+#[derive(Clone, Debug)]
+pub struct PassMove {
+    pub pass: String,
+    /// Index of the pass in the order the passes were recorded in
+    pub from: usize,
+    /// Position of the pass right after the move. Later moves may shift it again.
+    pub to: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for PassMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: {} -> {}: {}",
+            self.pass, self.from, self.to, self.reason
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct ScheduleCost {
+    transitions: usize,
+    peak_bytes: usize,
+}
+
+fn pass_accesses(pass: &RecordedPass) -> impl Iterator<Item = (&PassResourceRef, bool)> {
+    pass.read
+        .iter()
+        .map(|resource_ref| (resource_ref, false))
+        .chain(pass.write.iter().map(|resource_ref| (resource_ref, true)))
+}
+
+// Tracked state after accessing a resource in `state`, and whether that needs a transition.
+// Mirrors the rules of `ResourceStateTracker`.
+fn next_state(
+    current: Option<RenderResourceStates>,
+    state: RenderResourceStates,
+) -> (RenderResourceStates, bool) {
+    match current {
+        Some(current) if is_read_only(current) && current.contains(state) => (current, false),
+        Some(current) if is_read_only(current) && is_read_only(state) => (current | state, true),
+        _ => (state, true),
+    }
+}
+
+impl RenderGraph {
+    /// Reorders independent passes to group those needing the same resource states,
+    /// and to shrink the lifetimes of transient resources, lowering peak memory usage.
+    ///
+    /// A pass stays after every earlier pass it shares a resource with, unless both only read it,
+    /// and after the pass its resources were created in. Passes which opted out of culling
+    /// may have side effects outside of the graph, so they keep their relative order.
+    ///
+    /// Returns the moves made, along with the reasons for them.
+    pub fn reorder_passes(&mut self, reordering: PassReordering) -> Vec<PassMove> {
+        let pass_live = self.find_live_passes(&self.culling_roots());
+        let dependencies = self.pass_dependencies();
+        let mut moves = Vec::new();
+
+        let mut order = self.greedy_pass_order(&pass_live, &dependencies, &mut moves);
+
+        if let PassReordering::Search { time_budget } = reordering {
+            self.refine_pass_order(
+                &mut order,
+                &pass_live,
+                &dependencies,
+                time_budget,
+                &mut moves,
+            );
+        }
+
+        let mut new_position = vec![0; order.len()];
+        for (position, pass_idx) in order.iter().enumerate() {
+            new_position[*pass_idx] = position;
+        }
+
+        for resource in self.resources.iter_mut() {
+            if let GraphResourceInfo::Created(info) = resource {
+                info.create_pass_idx = new_position[info.create_pass_idx];
+            }
+        }
+
+        let mut passes: Vec<_> = self.passes.drain(..).map(Some).collect();
+        self.passes = order
+            .iter()
+            .map(|pass_idx| passes[*pass_idx].take().unwrap())
+            .collect();
+
+        for pass_move in moves.iter() {
+            log::debug!("Moved pass {}", pass_move);
+        }
+
+        moves
+    }
+
+    // For each pass, the earlier passes which must run before it.
+    fn pass_dependencies(&self) -> Vec<Vec<usize>> {
+        let mut dependencies = vec![Vec::new(); self.passes.len()];
+
+        for (pass_idx, pass) in self.passes.iter().enumerate() {
+            for (prev_idx, prev) in self.passes[..pass_idx].iter().enumerate() {
+                let conflicts = pass_accesses(pass).any(|(a, a_writes)| {
+                    pass_accesses(prev).any(|(b, b_writes)| {
+                        a.handle.id == b.handle.id
+                            && (a_writes || b_writes)
+                            && a.subresources.overlaps(&b.subresources)
+                    })
+                });
+
+                let uses_created = pass_accesses(pass).any(|(resource_ref, _)| {
+                    match &self.resources[resource_ref.handle.id as usize] {
+                        GraphResourceInfo::Created(info) => info.create_pass_idx == prev_idx,
+                        _ => false,
+                    }
+                });
+
+                let side_effects = pass.never_cull && prev.never_cull;
+
+                if conflicts || uses_created || side_effects {
+                    dependencies[pass_idx].push(prev_idx);
+                }
+            }
+        }
+
+        dependencies
+    }
+
+    fn initial_resource_states(&self) -> Vec<Option<RenderResourceStates>> {
+        self.resources
+            .iter()
+            .map(|resource| match resource {
+                GraphResourceInfo::Imported(info) => Some(info.state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn transient_size_bytes(&self, resource_id: usize) -> usize {
+        match &self.resources[resource_id] {
+            GraphResourceInfo::Created(info) => info.desc.size_bytes(),
+            _ => 0,
+        }
+    }
+
+    fn is_exported(&self, resource_id: usize) -> bool {
+        self.exported_resources
+            .iter()
+            .any(|exported| exported.handle.id as usize == resource_id)
+    }
+
+    // Builds an order by repeatedly picking the ready pass which needs the fewest transitions,
+    // breaking ties by the memory it allocates minus the memory it frees, and then
+    // by recording order.
+    fn greedy_pass_order(
+        &self,
+        pass_live: &[bool],
+        dependencies: &[Vec<usize>],
+        moves: &mut Vec<PassMove>,
+    ) -> Vec<usize> {
+        let mut states = self.initial_resource_states();
+        let mut scheduled = vec![false; self.passes.len()];
+        let mut allocated = vec![false; self.resources.len()];
+        let mut remaining_accesses = vec![0usize; self.resources.len()];
+
+        for (pass, _) in self
+            .passes
+            .iter()
+            .zip(pass_live.iter())
+            .filter(|(_, live)| **live)
+        {
+            for (resource_ref, _) in pass_accesses(pass) {
+                remaining_accesses[resource_ref.handle.id as usize] += 1;
+            }
+        }
+
+        let score = |pass_idx: usize,
+                     states: &[Option<RenderResourceStates>],
+                     allocated: &[bool],
+                     remaining_accesses: &[usize]|
+         -> (usize, i64) {
+            if !pass_live[pass_idx] {
+                return (0, 0);
+            }
+
+            let pass = &self.passes[pass_idx];
+            let mut transitions = 0;
+            let mut memory_delta = 0i64;
+
+            for (resource_ref, _) in pass_accesses(pass) {
+                let id = resource_ref.handle.id as usize;
+
+                if next_state(states[id], resource_ref.access_mode).1 {
+                    transitions += 1;
+                }
+
+                if !allocated[id] {
+                    memory_delta += self.transient_size_bytes(id) as i64;
+                } else if remaining_accesses[id] == 1 && !self.is_exported(id) {
+                    memory_delta -= self.transient_size_bytes(id) as i64;
+                }
+            }
+
+            (transitions, memory_delta)
+        };
+
+        let mut order = Vec::with_capacity(self.passes.len());
+
+        while order.len() < self.passes.len() {
+            let ready = (0..self.passes.len()).filter(|pass_idx| {
+                !scheduled[*pass_idx]
+                    && dependencies[*pass_idx]
+                        .iter()
+                        .all(|dependency| scheduled[*dependency])
+            });
+
+            let (first_ready, first_ready_score) = ready
+                .clone()
+                .next()
+                .map(|pass_idx| {
+                    (
+                        pass_idx,
+                        score(pass_idx, &states, &allocated, &remaining_accesses),
+                    )
+                })
+                .unwrap();
+
+            let (chosen, chosen_score) = ready
+                .map(|pass_idx| {
+                    (
+                        pass_idx,
+                        score(pass_idx, &states, &allocated, &remaining_accesses),
+                    )
+                })
+                .min_by_key(|(pass_idx, score)| (*score, *pass_idx))
+                .unwrap();
+
+            if chosen != first_ready && pass_live[chosen] {
+                moves.push(PassMove {
+                    pass: self.passes[chosen].name.clone(),
+                    from: chosen,
+                    to: order.len(),
+                    reason: format!(
+                        "ahead of {:?}: {} vs {} transitions, {} vs {} bytes allocated",
+                        self.passes[first_ready].name,
+                        chosen_score.0,
+                        first_ready_score.0,
+                        chosen_score.1,
+                        first_ready_score.1
+                    ),
+                });
+            }
+
+            scheduled[chosen] = true;
+            order.push(chosen);
+
+            if pass_live[chosen] {
+                let pass = &self.passes[chosen];
+                for (resource_ref, _) in pass_accesses(pass) {
+                    let id = resource_ref.handle.id as usize;
+                    states[id] = Some(next_state(states[id], resource_ref.access_mode).0);
+                    allocated[id] = true;
+                    remaining_accesses[id] -= 1;
+                }
+            }
+        }
+
+        order
+    }
+
+    fn schedule_cost(&self, order: &[usize], pass_live: &[bool]) -> ScheduleCost {
+        let mut states = self.initial_resource_states();
+        let mut transitions = 0;
+        let mut first_access = vec![None; self.resources.len()];
+        let mut last_access = vec![0; self.resources.len()];
+
+        for (position, pass_idx) in order.iter().enumerate() {
+            if !pass_live[*pass_idx] {
+                continue;
+            }
+
+            let pass = &self.passes[*pass_idx];
+            for (resource_ref, _) in pass_accesses(pass) {
+                let id = resource_ref.handle.id as usize;
+                let (state, transition) = next_state(states[id], resource_ref.access_mode);
+
+                states[id] = Some(state);
+                transitions += transition as usize;
+                first_access[id].get_or_insert(position);
+                last_access[id] = position;
+            }
+        }
+
+        // Change in live transient memory at each position
+        let mut memory_delta = vec![0i64; order.len() + 1];
+        for (id, first_access) in first_access.iter().enumerate() {
+            if let Some(first_access) = first_access {
+                let size = self.transient_size_bytes(id) as i64;
+                let end = if self.is_exported(id) {
+                    order.len()
+                } else {
+                    last_access[id] + 1
+                };
+
+                memory_delta[*first_access] += size;
+                memory_delta[end] -= size;
+            }
+        }
+
+        let peak_bytes = memory_delta
+            .iter()
+            .scan(0i64, |live_bytes, delta| {
+                *live_bytes += delta;
+                Some(*live_bytes)
+            })
+            .max()
+            .unwrap_or(0);
+
+        ScheduleCost {
+            transitions,
+            peak_bytes: peak_bytes as usize,
+        }
+    }
+
+    // Swaps adjacent independent passes while that lowers the cost of the schedule.
+    fn refine_pass_order(
+        &self,
+        order: &mut [usize],
+        pass_live: &[bool],
+        dependencies: &[Vec<usize>],
+        time_budget: Duration,
+        moves: &mut Vec<PassMove>,
+    ) {
+        let start_time = Instant::now();
+        let mut cost = self.schedule_cost(order, pass_live);
+
+        'search: loop {
+            let mut improved = false;
+
+            for position in 1..order.len() {
+                if start_time.elapsed() > time_budget {
+                    break 'search;
+                }
+
+                let (earlier, later) = (order[position - 1], order[position]);
+                if dependencies[later].contains(&earlier) {
+                    continue;
+                }
+
+                order.swap(position - 1, position);
+                let new_cost = self.schedule_cost(order, pass_live);
+
+                if new_cost < cost {
+                    moves.push(PassMove {
+                        pass: self.passes[later].name.clone(),
+                        from: later,
+                        to: position - 1,
+                        reason: format!(
+                            "swapped with {:?}: {} vs {} transitions, {} vs {} bytes at peak",
+                            self.passes[earlier].name,
+                            new_cost.transitions,
+                            cost.transitions,
+                            new_cost.peak_bytes,
+                            cost.peak_bytes
+                        ),
+                    });
+
+                    cost = new_cost;
+                    improved = true;
+                } else {
+                    order.swap(position - 1, position);
+                }
+            }
+
+            if !improved {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::*;
+    use render_core::{
+        handles::RenderResourceHandleAllocator,
+        types::{RenderFormat, RenderResourceType},
+    };
+
+    fn desc() -> TextureDesc {
+        TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float)
+    }
+
+    fn pass_names(rg: &RenderGraph) -> Vec<&str> {
+        rg.passes.iter().map(|pass| pass.name.as_str()).collect()
+    }
+
+    fn cost(rg: &RenderGraph) -> ScheduleCost {
+        let pass_live = rg.find_live_passes(&rg.culling_roots());
+        let order: Vec<usize> = (0..rg.passes.len()).collect();
+        rg.schedule_cost(&order, &pass_live)
+    }
+
+    // "write mip 0" needs a transition, while the reads of mip 1 don't,
+    // as the texture starts out in a read state.
+    fn mip_graph() -> RenderGraph {
+        let mut rg = RenderGraph::new();
+        let mut tex = rg.import(
+            RenderResourceHandleAllocator::new().allocate(RenderResourceType::Texture),
+            desc().mip_levels(2),
+            RenderResourceStates::PIXEL_SHADER_RESOURCE
+                | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+        );
+
+        let mut pass = rg.add_pass("write mip 0");
+        pass.write_subresources(&mut tex, SubresourceRange::mip(0));
+        pass.render(|_, _| Ok(()));
+
+        for name in ["read mip 1", "read mip 1 again"].iter() {
+            let mut pass = rg.add_pass(name);
+            pass.read_subresources(&tex, SubresourceRange::mip(1));
+            pass.never_cull();
+            pass.render(|_, _| Ok(()));
+        }
+
+        rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+        rg
+    }
+
+    // "a" and "b" are needed at the same time unless "read a" moves up.
+    fn memory_graph() -> RenderGraph {
+        let mut rg = RenderGraph::new();
+
+        let mut make = |name: &str| {
+            let mut pass = rg.add_pass(&format!("make {}", name));
+            let mut tex = pass.create(name, &desc());
+            pass.write(&mut tex);
+            pass.render(|_, _| Ok(()));
+            tex
+        };
+        let a = make("a");
+        let b = make("b");
+
+        for (name, tex) in [("read a", &a), ("read b", &b)].iter() {
+            let mut pass = rg.add_pass(name);
+            pass.read(*tex);
+            pass.never_cull();
+            pass.render(|_, _| Ok(()));
+        }
+
+        rg
+    }
+
+    #[test]
+    fn grouping_reads_saves_transitions() {
+        let mut rg = mip_graph();
+        let before = cost(&rg);
+
+        let moves = rg.reorder_passes(PassReordering::Deterministic);
+
+        // The reads can go first, but keep their order, as neither may be culled.
+        assert_eq!(
+            pass_names(&rg),
+            ["read mip 1", "read mip 1 again", "write mip 0"]
+        );
+        assert_eq!(
+            moves
+                .iter()
+                .map(|pass_move| (pass_move.pass.as_str(), pass_move.from, pass_move.to))
+                .collect::<Vec<_>>(),
+            [("read mip 1", 1, 0), ("read mip 1 again", 2, 1)]
+        );
+
+        let after = cost(&rg);
+        assert_eq!(before.transitions, 2);
+        assert_eq!(after.transitions, 1);
+    }
+
+    #[test]
+    fn reading_resources_early_lowers_peak_memory() {
+        let mut rg = memory_graph();
+        let before = cost(&rg);
+
+        rg.reorder_passes(PassReordering::Deterministic);
+
+        // Passes still come after the ones creating their resources
+        assert_eq!(pass_names(&rg), ["make a", "read a", "make b", "read b"]);
+
+        let after = cost(&rg);
+        assert_eq!(after.transitions, before.transitions);
+        assert_eq!(before.peak_bytes, 2 * desc().size_bytes());
+        assert_eq!(after.peak_bytes, desc().size_bytes());
+    }
+
+    #[test]
+    fn deterministic_reordering_is_reproducible() {
+        let reorder = || {
+            let mut rg = memory_graph();
+            let moves: Vec<String> = rg
+                .reorder_passes(PassReordering::Deterministic)
+                .iter()
+                .map(|pass_move| pass_move.to_string())
+                .collect();
+            let names: Vec<String> = pass_names(&rg).into_iter().map(str::to_owned).collect();
+            (names, moves)
+        };
+
+        assert_eq!(reorder(), reorder());
+    }
+}
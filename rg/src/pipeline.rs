@@ -1,4 +1,8 @@
-use render_core::{handles::RenderResourceHandle, state::RenderState};
+use render_core::{
+    handles::RenderResourceHandle,
+    state::RenderState,
+    types::{RenderFormat, RenderPrimitiveType},
+};
 use std::path::PathBuf;
 
 pub struct ComputePipeline {
@@ -6,14 +10,57 @@ pub struct ComputePipeline {
     pub group_size: [u32; 3],
     pub srvs: Vec<String>,
     pub uavs: Vec<String>,
+    /// Constant buffers the shader declares, in binding order
+    pub cbuffers: Vec<String>,
 }
 
 pub struct RasterPipeline {
     pub handle: RenderResourceHandle,
+    /// Views bound by the vertex and pixel shaders. The stages share one signature,
+    /// so the lists are merged by binding position, and the stages must agree
+    /// on the name at each position they both bind.
+    pub srvs: Vec<String>,
+    pub uavs: Vec<String>,
+    /// Constant buffers declared by the vertex and pixel shaders, merged the same way
+    pub cbuffers: Vec<String>,
+}
+
+/// One attribute within a vertex stream, matched to a vertex shader input by semantic.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct VertexElementDesc {
+    /// Semantic of the vertex shader input, including its index, e.g. "POSITION" or "TEXCOORD1"
+    pub semantic: String,
+    pub format: RenderFormat,
+    /// Offset in bytes from the start of the vertex
+    pub offset: u32,
+}
+
+/// Layout of the vertices in one vertex buffer bound via the draw binding set.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct VertexStreamDesc {
+    pub stride: u32,
+    pub elements: Vec<VertexElementDesc>,
+}
+
+pub struct RayTracingPipeline {
+    pub pipeline_state: RenderResourceHandle,
+    /// One entry for each of the raygen, miss and hit shaders
+    pub shader_table: RenderResourceHandle,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct RayTracingPipelineDesc {
+    pub raygen_shader: PathBuf,
+    pub miss_shader: PathBuf,
+    pub hit_shader: PathBuf,
 }
 
 pub struct RasterPipelineDesc {
     pub vertex_shader: PathBuf,
     pub pixel_shader: PathBuf,
     pub render_state: RenderState,
+    /// Need to provide all the inputs of the vertex shader, other than system values such as
+    /// `SV_VertexID`. Left empty for shaders which fetch vertices themselves.
+    pub vertex_streams: Vec<VertexStreamDesc>,
+    pub primitive_type: RenderPrimitiveType,
 }
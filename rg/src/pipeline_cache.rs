@@ -1,19 +1,27 @@
 use crate::{
-    pipeline::{ComputePipeline, RasterPipeline},
-    shader_cache::{ShaderCache, ShaderCacheEntry},
+    format_component_count,
+    pipeline::{
+        ComputePipeline, RasterPipeline, RayTracingPipeline, RayTracingPipelineDesc,
+        VertexStreamDesc,
+    },
+    shader_cache::{RasterShaderCacheEntry, ShaderCache, ShaderCacheEntry, ShaderVertexInput},
     RasterPipelineDesc, RenderGraphExecutionParams, RenderTarget,
 };
 use render_core::{
-    constants::{MAX_RENDER_TARGET_COUNT, MAX_SHADER_TYPE},
+    constants::{
+        MAX_RENDER_TARGET_COUNT, MAX_SHADER_TYPE, MAX_VERTEX_ELEMENTS, MAX_VERTEX_STREAMS,
+    },
     handles::RenderResourceHandle,
     state::*,
     types::{
+        RayTracingPipelineStateDesc, RayTracingProgramType, RayTracingShaderTableDesc,
         RenderFormat, RenderPrimitiveType, RenderResourceType, RenderShaderParameter,
-        RenderShaderSignatureDesc, RenderShaderType,
+        RenderShaderSignatureDesc, RenderShaderType, RenderVertexElement,
     },
 };
 use std::{
     collections::HashMap,
+    hash::{Hash, Hasher},
     path::Path,
     sync::{Arc, RwLock},
 };
@@ -23,7 +31,10 @@ struct RasterPipelineKey {
     vertex_shader: RenderResourceHandle,
     pixel_shader: RenderResourceHandle,
     render_target_formats: [RenderFormat; MAX_RENDER_TARGET_COUNT],
+    depth_stencil_format: RenderFormat,
     render_state_hash: u64,
+    vertex_streams_hash: u64,
+    primitive_type: RenderPrimitiveType,
 }
 
 impl RasterPipelineKey {
@@ -35,6 +46,133 @@ impl RasterPipelineKey {
 #[derive(Clone, Copy, Hash, Eq, PartialEq, Default)]
 struct RasterPipelineId(usize);
 
+fn raster_shader(entry: &ShaderCacheEntry) -> &RasterShaderCacheEntry {
+    match entry {
+        ShaderCacheEntry::Raster(shader) => shader,
+        ShaderCacheEntry::Compute(..) | ShaderCacheEntry::RayTracing(..) => unreachable!(),
+    }
+}
+
+// Views are bound by position, and all stages of a pipeline share one table,
+// so a view used by several stages needs to be declared at the same position in each.
+fn merge_stage_views(kind: &str, stages: &[&[String]]) -> anyhow::Result<Vec<String>> {
+    let mut merged: Vec<String> = Vec::new();
+
+    for views in stages {
+        for (idx, name) in views.iter().enumerate() {
+            match merged.get(idx) {
+                Some(existing) if existing != name => anyhow::bail!(
+                    "Shader stages disagree on {} {}: {:?} vs {:?}",
+                    kind,
+                    idx,
+                    existing,
+                    name
+                ),
+                Some(_) => {}
+                None => merged.push(name.clone()),
+            }
+        }
+    }
+
+    Ok(merged)
+}
+
+struct VertexInputLayout {
+    element_count: u32,
+    elements: [RenderVertexElement; MAX_VERTEX_ELEMENTS],
+    strides: [u32; MAX_VERTEX_STREAMS],
+}
+
+// Matches the elements of `streams` to the inputs of the vertex shader by semantic.
+// Like in HLSL, semantics are case-insensitive.
+fn vertex_input_layout(
+    streams: &[VertexStreamDesc],
+    shader_inputs: &[ShaderVertexInput],
+) -> anyhow::Result<VertexInputLayout> {
+    anyhow::ensure!(
+        streams.len() <= MAX_VERTEX_STREAMS,
+        "{} vertex streams specified, but at most {} are supported",
+        streams.len(),
+        MAX_VERTEX_STREAMS
+    );
+
+    let mut layout = VertexInputLayout {
+        element_count: 0,
+        elements: Default::default(),
+        strides: Default::default(),
+    };
+    let mut provided = vec![false; shader_inputs.len()];
+
+    for (stream_idx, stream) in streams.iter().enumerate() {
+        layout.strides[stream_idx] = stream.stride;
+
+        for element in stream.elements.iter() {
+            let input_idx = shader_inputs
+                .iter()
+                .position(|input| input.semantic.eq_ignore_ascii_case(&element.semantic))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "The vertex shader has no input with the semantic {:?}",
+                        element.semantic
+                    )
+                })?;
+
+            anyhow::ensure!(
+                !provided[input_idx],
+                "Vertex input {:?} is provided by more than one element",
+                element.semantic
+            );
+            provided[input_idx] = true;
+
+            let input = &shader_inputs[input_idx];
+            let component_count = format_component_count(element.format).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unsupported format {:?} for vertex input {:?}",
+                    element.format,
+                    element.semantic
+                )
+            })?;
+
+            // Components the shader reads but the format lacks are filled in with (0, 0, 1),
+            // e.g. the w of a float4 position fed from float3 data. Extra ones are dropped,
+            // which works, but likely isn't intended.
+            if component_count > input.component_count {
+                log::warn!(
+                    "Vertex input {:?} has {} components, but its element format {:?} has {}",
+                    input.semantic,
+                    input.component_count,
+                    element.format,
+                    component_count
+                );
+            }
+
+            let element_idx = layout.element_count as usize;
+            anyhow::ensure!(
+                element_idx < MAX_VERTEX_ELEMENTS,
+                "At most {} vertex elements are supported",
+                MAX_VERTEX_ELEMENTS
+            );
+
+            layout.elements[element_idx] = RenderVertexElement {
+                stream: stream_idx as u32,
+                location: input.location,
+                format: element.format,
+                offset: element.offset,
+            };
+            layout.element_count += 1;
+        }
+    }
+
+    if let Some(missing) = provided.iter().position(|provided| !provided) {
+        anyhow::bail!(
+            "The vertex shader input {:?} is not provided by any vertex stream",
+            shader_inputs[missing].semantic
+        );
+    }
+
+    Ok(layout)
+}
+
 type CsToPipeline = HashMap<RenderResourceHandle, Arc<ComputePipeline>>;
 type RasterToPipelines = HashMap<RenderResourceHandle, Vec<RasterPipelineId>>;
 
@@ -43,6 +181,12 @@ struct RasterPipelineEntry {
     key: RasterPipelineKey,
 }
 
+struct RayTracingPipelineEntry {
+    pipeline: Arc<RayTracingPipeline>,
+    // Programs the pipeline was built from; it's rebuilt when any of them is retired.
+    programs: [RenderResourceHandle; 3],
+}
+
 #[derive(Default)]
 pub struct Pipelines {
     compute_shader_to_pipeline: CsToPipeline,
@@ -59,6 +203,8 @@ pub struct Pipelines {
 
     // Next unused RasterPipelineId.
     next_raster_pipeline_id: RasterPipelineId,
+
+    ray_tracing_pipelines: HashMap<RayTracingPipelineDesc, RayTracingPipelineEntry>,
 }
 
 pub struct PipelineCache {
@@ -97,7 +243,9 @@ impl PipelineCache {
             std::collections::hash_map::Entry::Vacant(vacant) => {
                 let shader = match &*shader {
                     ShaderCacheEntry::Compute(shader) => shader,
-                    ShaderCacheEntry::Raster(..) => unreachable!(),
+                    ShaderCacheEntry::Raster(..) | ShaderCacheEntry::RayTracing(..) => {
+                        unreachable!()
+                    }
                 };
 
                 let shader_handle = shader.shader_handle;
@@ -126,6 +274,7 @@ impl PipelineCache {
                     group_size: shader.group_size,
                     srvs: shader.srvs.clone(),
                     uavs: shader.uavs.clone(),
+                    cbuffers: shader.cbuffers.clone(),
                 });
 
                 vacant.insert(pipeline_entry.clone());
@@ -200,8 +349,10 @@ impl PipelineCache {
             }
         }
 
-        let vertex_shader = vertex_shader.entry?.shader_handle();
-        let pixel_shader = pixel_shader.entry?.shader_handle();
+        let vertex_shader_entry = vertex_shader.entry?;
+        let pixel_shader_entry = pixel_shader.entry?;
+        let vertex_shader = vertex_shader_entry.shader_handle();
+        let pixel_shader = pixel_shader_entry.shader_handle();
 
         let render_state_blob = bincode::serialize(&desc.render_state).unwrap();
         let render_state_hash = wyhash::wyhash(&render_state_blob, 0);
@@ -216,11 +367,25 @@ impl PipelineCache {
             }
         }
 
+        let depth_stencil_format = render_target
+            .depth
+            .as_ref()
+            .map_or(RenderFormat::Unknown, |depth| depth.desc.format);
+
+        let vertex_streams_hash = {
+            let mut hasher = wyhash::WyHash::with_seed(0);
+            desc.vertex_streams.hash(&mut hasher);
+            hasher.finish()
+        };
+
         let pipeline_key = RasterPipelineKey {
             vertex_shader,
             pixel_shader,
             render_state_hash,
             render_target_formats,
+            depth_stencil_format,
+            vertex_streams_hash,
+            primitive_type: desc.primitive_type,
         };
 
         if let Some(key) = pipelines
@@ -232,6 +397,31 @@ impl PipelineCache {
 
         println!("Creating a new raster pipeline");
 
+        let stages = [
+            raster_shader(&vertex_shader_entry),
+            raster_shader(&pixel_shader_entry),
+        ];
+        let srvs = merge_stage_views(
+            "SRV",
+            &stages.iter().map(|s| s.srvs.as_slice()).collect::<Vec<_>>(),
+        )?;
+        let uavs = merge_stage_views(
+            "UAV",
+            &stages.iter().map(|s| s.uavs.as_slice()).collect::<Vec<_>>(),
+        )?;
+        let cbuffers = merge_stage_views(
+            "constant buffer",
+            &stages
+                .iter()
+                .map(|s| s.cbuffers.as_slice())
+                .collect::<Vec<_>>(),
+        )?;
+
+        let vertex_input_layout = vertex_input_layout(
+            &desc.vertex_streams,
+            &raster_shader(&vertex_shader_entry).vertex_inputs,
+        )?;
+
         let pipeline_handle = params
             .handles
             .allocate_persistent(RenderResourceType::GraphicsPipelineState);
@@ -245,29 +435,30 @@ impl PipelineCache {
             &RenderGraphicsPipelineStateDesc {
                 shaders,
                 shader_signature: RenderShaderSignatureDesc::new(
-                    // TODO
                     &[RenderShaderParameter::new(
-                        0, //shader.srvs.len() as u32,
-                        0, // shader.uavs.len() as u32,
+                        srvs.len() as u32,
+                        uavs.len() as u32,
                     )],
                     &[],
                 ),
                 render_state: desc.render_state,
-                vertex_element_count: 0,
-                vertex_elements: Default::default(),
-                vertex_buffer_strides: Default::default(),
-                primitive_type: RenderPrimitiveType::TriangleList, // TODO
+                vertex_element_count: vertex_input_layout.element_count,
+                vertex_elements: vertex_input_layout.elements,
+                vertex_buffer_strides: vertex_input_layout.strides,
+                primitive_type: desc.primitive_type,
                 render_target_count,
                 render_target_write_masks: Default::default(),
                 render_target_formats,
-                //depth_stencil_format: RenderFormat::D32Float, // TODO
-                depth_stencil_format: RenderFormat::Unknown,
+                depth_stencil_format,
             },
             "raster pipeline".into(),
         )?;
 
         let pipeline = Arc::new(RasterPipeline {
             handle: pipeline_handle,
+            srvs,
+            uavs,
+            cbuffers,
         });
 
         let entry = RasterPipelineEntry {
@@ -286,4 +477,155 @@ impl PipelineCache {
 
         Ok(res)
     }
+
+    pub fn get_or_load_ray_tracing(
+        &self,
+        params: &RenderGraphExecutionParams<'_, '_, '_>,
+        desc: RayTracingPipelineDesc,
+    ) -> anyhow::Result<Arc<RayTracingPipeline>> {
+        let programs = [
+            (RayTracingProgramType::RayGen, &desc.raygen_shader),
+            (RayTracingProgramType::Miss, &desc.miss_shader),
+            (RayTracingProgramType::Hit, &desc.hit_shader),
+        ]
+        .iter()
+        .map(|(program_type, path)| {
+            self.shader_cache
+                .get_or_load_ray_tracing(params, *program_type, path)
+        })
+        .collect::<Vec<_>>();
+
+        let mut pipelines = self.pipelines.write().unwrap();
+
+        // Pipelines and their shader tables can't be patched, so any using retired programs
+        // are dropped, and rebuilt below when requested.
+        let mut retired_programs = Vec::new();
+        let mut program_entries = Vec::with_capacity(programs.len());
+        for program in programs {
+            retired_programs.extend(program.retired.map(|retired| retired.shader_handle()));
+            program_entries.push(program.entry);
+        }
+
+        pipelines.ray_tracing_pipelines.retain(|_, entry| {
+            !entry
+                .programs
+                .iter()
+                .any(|program| retired_programs.contains(program))
+        });
+
+        let mut program_handles = [RenderResourceHandle::default(); 3];
+        for (handle, entry) in program_handles.iter_mut().zip(program_entries) {
+            *handle = entry?.shader_handle();
+        }
+
+        if let Some(entry) = pipelines.ray_tracing_pipelines.get(&desc) {
+            return Ok(entry.pipeline.clone());
+        }
+
+        let pipeline_state = params
+            .handles
+            .allocate_persistent(RenderResourceType::RayTracingPipelineState);
+
+        params.device.create_ray_tracing_pipeline_state(
+            pipeline_state,
+            &RayTracingPipelineStateDesc {
+                programs: program_handles.to_vec(),
+            },
+            "rt pipeline state".into(),
+        )?;
+
+        let shader_table = params
+            .handles
+            .allocate_persistent(RenderResourceType::RayTracingShaderTable);
+
+        params.device.create_ray_tracing_shader_table(
+            shader_table,
+            &RayTracingShaderTableDesc {
+                pipeline_state,
+                raygen_entry_count: 1,
+                hit_entry_count: 1,
+                miss_entry_count: 1,
+            },
+            "sbt".into(),
+        )?;
+
+        let pipeline = Arc::new(RayTracingPipeline {
+            pipeline_state,
+            shader_table,
+        });
+
+        pipelines.ray_tracing_pipelines.insert(
+            desc,
+            RayTracingPipelineEntry {
+                pipeline: pipeline.clone(),
+                programs: program_handles,
+            },
+        );
+
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::VertexElementDesc;
+
+    fn position_stream(semantic: &str, format: RenderFormat) -> VertexStreamDesc {
+        VertexStreamDesc {
+            stride: 12,
+            elements: vec![VertexElementDesc {
+                semantic: semantic.to_owned(),
+                format,
+                offset: 0,
+            }],
+        }
+    }
+
+    fn position_input() -> ShaderVertexInput {
+        ShaderVertexInput {
+            semantic: "POSITION".to_owned(),
+            location: 3,
+            component_count: 3,
+        }
+    }
+
+    #[test]
+    fn semantics_match_case_insensitively() {
+        let layout = vertex_input_layout(
+            &[position_stream("Position", RenderFormat::R32g32b32Float)],
+            &[position_input()],
+        )
+        .unwrap();
+
+        assert_eq!(layout.element_count, 1);
+        assert_eq!(layout.elements[0].location, 3);
+        assert_eq!(layout.strides[0], 12);
+    }
+
+    #[test]
+    fn missing_components_are_filled_in() {
+        let float4_position = ShaderVertexInput {
+            component_count: 4,
+            ..position_input()
+        };
+
+        let layout = vertex_input_layout(
+            &[position_stream("POSITION", RenderFormat::R32g32b32Float)],
+            &[float4_position],
+        )
+        .unwrap();
+
+        assert_eq!(layout.element_count, 1);
+        assert_eq!(layout.elements[0].format, RenderFormat::R32g32b32Float);
+    }
+
+    #[test]
+    fn formats_without_known_components_are_an_error() {
+        assert!(vertex_input_layout(
+            &[position_stream("POSITION", RenderFormat::Unknown)],
+            &[position_input()],
+        )
+        .is_err());
+    }
 }
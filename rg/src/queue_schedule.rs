@@ -0,0 +1,192 @@
+use crate::graph::RenderGraph;
+use render_core::types::RenderResourceStates;
+use std::{collections::HashMap, ops::Range};
+
+/// The GPU queue a pass is recorded for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum QueueType {
+    Graphics,
+    /// Runs concurrently with graphics work, synchronized only where passes
+    /// on the two queues share resources. See `RenderGraph::add_async_compute_pass`.
+    AsyncCompute,
+}
+
+impl Default for QueueType {
+    fn default() -> Self {
+        Self::Graphics
+    }
+}
+
+impl QueueType {
+    fn index(self) -> usize {
+        match self {
+            Self::Graphics => 0,
+            Self::AsyncCompute => 1,
+        }
+    }
+}
+
+/// Command lists recorded by `RenderGraph::execute`, to be compiled together in order,
+/// and submitted to `queue` after the submissions in `wait_for` have finished on the GPU.
+///
+/// Submissions must be made in order; each only waits for earlier ones, and every
+/// submission is waited for at most once. The last one is always on the graphics queue,
+/// and waits for all async compute work of the graph.
+#[derive(Clone, Debug)]
+pub struct QueueSubmission {
+    pub queue: QueueType,
+    pub wait_for: Vec<usize>,
+    /// Indices of the command lists making up this submission
+    pub command_lists: Range<usize>,
+}
+
+/// States which only the graphics queue can transition resources out of.
+pub(crate) fn graphics_only_states() -> RenderResourceStates {
+    RenderResourceStates::RENDER_TARGET
+        | RenderResourceStates::DEPTH_WRITE
+        | RenderResourceStates::DEPTH_READ
+        | RenderResourceStates::PIXEL_SHADER_RESOURCE
+}
+
+pub(crate) struct QueueSchedule {
+    pub submissions: Vec<QueueSubmission>,
+    /// Index of the submission each live pass is recorded into
+    pub pass_submission: Vec<Option<usize>>,
+    /// Index of the command list each live pass is recorded into
+    pub pass_command_list: Vec<Option<usize>>,
+    pub command_list_count: usize,
+    /// Submission which transitions exported resources into their final states
+    pub final_submission: usize,
+}
+
+// GPU resource a graph resource maps to. Created resources may share one via aliasing.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+enum PhysicalResource {
+    Aliased(usize),
+    Graph(u32),
+}
+
+impl RenderGraph {
+    /// Splits live passes into submissions to the graphics and async compute queues.
+    ///
+    /// Passes stay in recording order within each queue. Whenever a pass accesses
+    /// a GPU resource last touched by the other queue, the other queue's submission is
+    /// closed, and the pass starts a new submission which waits for it. Any shared access
+    /// counts, including read-after-read, as barriers on one queue could otherwise
+    /// transition resources still in use on the other.
+    pub(crate) fn schedule_queues(
+        &self,
+        pass_live: &[bool],
+        resource_to_aliased: &[Option<usize>],
+    ) -> QueueSchedule {
+        let physical_resource = |id: u32| match resource_to_aliased[id as usize] {
+            Some(aliased_idx) => PhysicalResource::Aliased(aliased_idx),
+            None => PhysicalResource::Graph(id),
+        };
+
+        let mut submissions: Vec<QueueSubmission> = Vec::new();
+        let mut pass_submission = vec![None; self.passes.len()];
+
+        // Submission currently being recorded into, per queue
+        let mut open: [Option<usize>; 2] = [None; 2];
+        // Latest submission on the other queue which each queue has waited for
+        let mut waited: [Option<usize>; 2] = [None; 2];
+        // Latest submission per queue which accessed each resource
+        let mut last_access: HashMap<PhysicalResource, [Option<usize>; 2]> = HashMap::new();
+
+        for (pass_idx, pass) in self.passes.iter().enumerate() {
+            if !pass_live[pass_idx] {
+                continue;
+            }
+
+            let queue = pass.queue.index();
+            let other_queue = 1 - queue;
+
+            let dependency = pass
+                .read
+                .iter()
+                .chain(pass.write.iter())
+                .filter_map(|resource_ref| {
+                    last_access
+                        .get(&physical_resource(resource_ref.handle.id))
+                        .and_then(|last| last[other_queue])
+                })
+                .max()
+                .filter(|dependency| waited[queue].map_or(true, |waited| *dependency > waited));
+
+            if let Some(dependency) = dependency {
+                // Nothing recorded after this point may end up in the submission being waited for.
+                if open[other_queue] == Some(dependency) {
+                    open[other_queue] = None;
+                }
+
+                // Waits happen at the start of submissions.
+                open[queue] = None;
+                waited[queue] = Some(dependency);
+            }
+
+            let submission = *open[queue].get_or_insert_with(|| {
+                submissions.push(QueueSubmission {
+                    queue: pass.queue,
+                    wait_for: dependency.into_iter().collect(),
+                    command_lists: 0..0,
+                });
+                submissions.len() - 1
+            });
+
+            pass_submission[pass_idx] = Some(submission);
+
+            for resource_ref in pass.read.iter().chain(pass.write.iter()) {
+                last_access
+                    .entry(physical_resource(resource_ref.handle.id))
+                    .or_default()[queue] = Some(submission);
+            }
+        }
+
+        // Join all async compute work back into the graphics queue.
+        let graphics = QueueType::Graphics.index();
+        let join = submissions
+            .iter()
+            .rposition(|submission| submission.queue == QueueType::AsyncCompute)
+            .filter(|last_compute| waited[graphics].map_or(true, |waited| *last_compute > waited));
+
+        let final_submission = match (join, open[graphics]) {
+            (None, Some(submission)) => submission,
+            _ => {
+                submissions.push(QueueSubmission {
+                    queue: QueueType::Graphics,
+                    wait_for: join.into_iter().collect(),
+                    command_lists: 0..0,
+                });
+                submissions.len() - 1
+            }
+        };
+
+        // Split submissions into groups of consecutive passes to be recorded in parallel.
+        let mut pass_command_list = vec![None; self.passes.len()];
+        let mut command_list_count = 0;
+
+        for (submission_idx, submission) in submissions.iter_mut().enumerate() {
+            let passes: Vec<usize> = (0..self.passes.len())
+                .filter(|pass_idx| pass_submission[*pass_idx] == Some(submission_idx))
+                .collect();
+
+            let list_count = self.recording_threads.min(passes.len()).max(1);
+            for (i, pass_idx) in passes.iter().enumerate() {
+                pass_command_list[*pass_idx] =
+                    Some(command_list_count + i * list_count / passes.len());
+            }
+
+            submission.command_lists = command_list_count..command_list_count + list_count;
+            command_list_count += list_count;
+        }
+
+        QueueSchedule {
+            submissions,
+            pass_submission,
+            pass_command_list,
+            command_list_count,
+            final_submission,
+        }
+    }
+}
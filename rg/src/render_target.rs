@@ -1,8 +1,11 @@
-use crate::{GpuRt, Ref, Texture};
+use crate::resource::{
+    GpuDsv, GpuDsvReadOnly, GpuRt, GraphRawResourceHandle, Ref, SubresourceRange, Texture,
+    TextureDesc,
+};
 use render_core::{
     constants::MAX_RENDER_TARGET_COUNT,
     state::{RenderDrawState, RenderScissorRect, RenderViewportRect},
-    types::RenderTargetInfo,
+    types::{DepthStencilTargetInfo, RenderTargetInfo},
 };
 
 pub struct ColorAttachment {
@@ -21,8 +24,51 @@ impl From<(Ref<Texture, GpuRt>, RenderTargetInfo)> for ColorAttachment {
 
 pub type ColorAttachments = [Option<ColorAttachment>; MAX_RENDER_TARGET_COUNT];
 
+/// Created from a reference obtained via `PassBuilder::depth_stencil`
+/// or `PassBuilder::depth_stencil_read_only`.
+pub struct DepthAttachment {
+    pub(crate) handle: GraphRawResourceHandle,
+    pub(crate) desc: TextureDesc,
+    pub(crate) subresources: SubresourceRange,
+    pub(crate) read_only: bool,
+    pub info: DepthStencilTargetInfo,
+}
+
+impl DepthAttachment {
+    pub fn desc(&self) -> &TextureDesc {
+        &self.desc
+    }
+}
+
+impl From<(Ref<Texture, GpuDsv>, DepthStencilTargetInfo)> for DepthAttachment {
+    fn from(v: (Ref<Texture, GpuDsv>, DepthStencilTargetInfo)) -> Self {
+        Self {
+            handle: v.0.handle,
+            desc: v.0.desc,
+            subresources: v.0.subresources,
+            read_only: false,
+            info: v.1,
+        }
+    }
+}
+
+impl From<(Ref<Texture, GpuDsvReadOnly>, DepthStencilTargetInfo)> for DepthAttachment {
+    fn from(v: (Ref<Texture, GpuDsvReadOnly>, DepthStencilTargetInfo)) -> Self {
+        Self {
+            handle: v.0.handle,
+            desc: v.0.desc,
+            subresources: v.0.subresources,
+            read_only: true,
+            info: v.1,
+        }
+    }
+}
+
 pub struct RenderTarget {
     pub(crate) color: ColorAttachments,
+    pub(crate) depth: Option<DepthAttachment>,
+    viewport: Option<RenderViewportRect>,
+    scissor: Option<RenderScissorRect>,
 }
 
 pub trait IntoColorAttachments {
@@ -63,34 +109,67 @@ impl RenderTarget {
     pub fn new(color: impl IntoColorAttachments) -> Self {
         Self {
             color: color.into_color_attachments(),
+            depth: None,
+            viewport: None,
+            scissor: None,
+        }
+    }
+
+    /// A target without color attachments, e.g. for shadow maps or depth pre-passes.
+    pub fn depth_only(depth: impl Into<DepthAttachment>) -> Self {
+        Self {
+            color: array_init::array_init(|_| None),
+            depth: Some(depth.into()),
+            viewport: None,
+            scissor: None,
         }
     }
+
+    pub fn with_depth(mut self, depth: impl Into<DepthAttachment>) -> Self {
+        self.depth = Some(depth.into());
+        self
+    }
+
+    /// Overrides the default viewport, which covers the whole of the first attachment.
+    pub fn with_viewport(mut self, viewport: RenderViewportRect) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    /// Overrides the default scissor rect, which covers the whole of the first attachment.
+    pub fn with_scissor(mut self, scissor: RenderScissorRect) -> Self {
+        self.scissor = Some(scissor);
+        self
+    }
 }
 
 impl RenderTarget {
     pub fn to_draw_state(self: &RenderTarget) -> RenderDrawState {
-        let [width, height] = self.color[0]
-            .as_ref()
-            .expect("render target color attachment")
-            .texture
-            .desc()
-            .dims();
+        let [width, height] = if let Some(color) = &self.color[0] {
+            color
+                .texture
+                .desc()
+                .mip_dims(color.texture.subresources().base_mip)
+        } else {
+            let depth = self.depth.as_ref().expect("render target attachment");
+            depth.desc.mip_dims(depth.subresources.base_mip)
+        };
 
         RenderDrawState {
-            viewport: Some(RenderViewportRect {
+            viewport: Some(self.viewport.unwrap_or(RenderViewportRect {
                 x: 0.0,
                 y: 0.0,
                 width: width as f32,
                 height: height as f32,
                 min_z: 0.0,
                 max_z: 1.0,
-            }),
-            scissor: Some(RenderScissorRect {
+            })),
+            scissor: Some(self.scissor.unwrap_or(RenderScissorRect {
                 x: 0,
                 y: 0,
                 width: width as i32,
                 height: height as i32,
-            }),
+            })),
             stencil_ref: 0,
         }
     }
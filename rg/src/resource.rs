@@ -1,9 +1,15 @@
-use render_core::{handles::*, types::RenderFormat};
+use render_core::{
+    handles::*,
+    types::{RenderBindFlags, RenderFormat},
+};
 use std::marker::PhantomData;
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Texture;
 
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Buffer;
+
 pub trait Resource {
     type Desc: ResourceDesc;
 }
@@ -12,28 +18,296 @@ impl Resource for Texture {
     type Desc = TextureDesc;
 }
 
+impl Resource for Buffer {
+    type Desc = BufferDesc;
+}
+
 pub trait ResourceDesc: Clone + std::fmt::Debug + Into<crate::graph::GraphResourceDesc> {
     type Resource: Resource;
+
+    /// Number of mip levels and array slices; `SubresourceRange`s are resolved against these.
+    fn subresource_counts(&self) -> (u32, u32);
+}
+
+/// A range of mip levels and array slices of a texture, e.g. for reading one mip
+/// and writing the next one within a single pass. Buffers have just one subresource.
+///
+/// Counts of `u32::MAX` mean "all remaining", and are resolved once the range
+/// is used with a resource.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SubresourceRange {
+    pub base_mip: u32,
+    pub mip_count: u32,
+    pub base_slice: u32,
+    pub slice_count: u32,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl SubresourceRange {
+    pub fn all() -> Self {
+        Self {
+            base_mip: 0,
+            mip_count: u32::MAX,
+            base_slice: 0,
+            slice_count: u32::MAX,
+        }
+    }
+
+    /// A single mip level, across all slices.
+    pub fn mip(mip: u32) -> Self {
+        Self::mips(mip, 1)
+    }
+
+    pub fn mips(base_mip: u32, mip_count: u32) -> Self {
+        Self {
+            base_mip,
+            mip_count,
+            ..Self::all()
+        }
+    }
+
+    pub fn slices(mut self, base_slice: u32, slice_count: u32) -> Self {
+        self.base_slice = base_slice;
+        self.slice_count = slice_count;
+        self
+    }
+
+    pub(crate) fn is_within(&self, (mip_levels, slice_count): (u32, u32)) -> bool {
+        self.base_mip < mip_levels && self.base_slice < slice_count
+    }
+
+    pub(crate) fn resolve(self, (mip_levels, slice_count): (u32, u32)) -> Self {
+        assert!(
+            self.is_within((mip_levels, slice_count)),
+            "Subresource range {:?} out of bounds of a resource with {} mips and {} slices",
+            self,
+            mip_levels,
+            slice_count
+        );
+
+        Self {
+            base_mip: self.base_mip,
+            mip_count: self.mip_count.min(mip_levels - self.base_mip),
+            base_slice: self.base_slice,
+            slice_count: self.slice_count.min(slice_count - self.base_slice),
+        }
+    }
+
+    pub(crate) fn overlaps(&self, other: &Self) -> bool {
+        let ranges_overlap = |base0: u32, count0: u32, base1: u32, count1: u32| {
+            base0 < base1.saturating_add(count1) && base1 < base0.saturating_add(count0)
+        };
+
+        ranges_overlap(
+            self.base_mip,
+            self.mip_count,
+            other.base_mip,
+            other.mip_count,
+        ) && ranges_overlap(
+            self.base_slice,
+            self.slice_count,
+            other.base_slice,
+            other.slice_count,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum TextureType {
+    Tex2d,
+    Tex3d,
+    Cube,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct TextureDesc {
+    pub texture_type: TextureType,
     pub width: u32,
     pub height: u32,
+    /// Only meaningful for 3D textures; 1 otherwise
+    pub depth: u32,
+    pub mip_levels: u32,
+    /// Number of array elements; for cube maps, the number of cubes
+    pub array_elements: u32,
+    pub sample_count: u32,
     pub format: RenderFormat,
 }
 
 impl ResourceDesc for TextureDesc {
     type Resource = Texture;
+
+    fn subresource_counts(&self) -> (u32, u32) {
+        (self.mip_levels, self.slice_count())
+    }
 }
 
 impl TextureDesc {
+    pub fn new_2d(width: u32, height: u32, format: RenderFormat) -> Self {
+        Self {
+            texture_type: TextureType::Tex2d,
+            width,
+            height,
+            depth: 1,
+            mip_levels: 1,
+            array_elements: 1,
+            sample_count: 1,
+            format,
+        }
+    }
+
+    pub fn new_3d(width: u32, height: u32, depth: u32, format: RenderFormat) -> Self {
+        Self {
+            texture_type: TextureType::Tex3d,
+            depth,
+            ..Self::new_2d(width, height, format)
+        }
+    }
+
+    pub fn new_cube(size: u32, format: RenderFormat) -> Self {
+        Self {
+            texture_type: TextureType::Cube,
+            ..Self::new_2d(size, size, format)
+        }
+    }
+
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    /// Sets the mip count to produce a full mip chain down to 1x1.
+    pub fn all_mip_levels(mut self) -> Self {
+        let max_dim = self.width.max(self.height).max(self.depth);
+        self.mip_levels = 32 - max_dim.leading_zeros();
+        self
+    }
+
+    pub fn array_elements(mut self, array_elements: u32) -> Self {
+        self.array_elements = array_elements;
+        self
+    }
+
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
     pub fn dims(self) -> [u32; 2] {
         [self.width, self.height]
     }
+
+    pub fn mip_dims(self, mip: u32) -> [u32; 2] {
+        [(self.width >> mip).max(1), (self.height >> mip).max(1)]
+    }
+
+    /// Number of 2D slices per mip level, counting cube faces and array elements.
+    pub fn slice_count(&self) -> u32 {
+        match self.texture_type {
+            TextureType::Cube => self.array_elements * 6,
+            TextureType::Tex2d | TextureType::Tex3d => self.array_elements,
+        }
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        let texel_count: usize = (0..self.mip_levels)
+            .map(|mip| {
+                (self.width >> mip).max(1) as usize
+                    * (self.height >> mip).max(1) as usize
+                    * (self.depth >> mip).max(1) as usize
+            })
+            .sum();
+
+        texel_count
+            * self.slice_count() as usize
+            * self.sample_count as usize
+            * format_bytes_per_pixel(self.format)
+    }
+}
+
+pub fn format_bytes_per_pixel(format: RenderFormat) -> usize {
+    match format {
+        RenderFormat::R32g32b32a32Float => 16,
+        RenderFormat::R32g32b32Float => 12,
+        RenderFormat::R16g16b16a16Float | RenderFormat::R32g32Float => 8,
+        RenderFormat::R10g10b10a2Unorm
+        | RenderFormat::R8g8b8a8Unorm
+        | RenderFormat::R16g16Float
+        | RenderFormat::R32Float
+        | RenderFormat::D32Float => 4,
+        RenderFormat::R16Float => 2,
+        RenderFormat::R8Unorm => 1,
+        // Conservative estimate for anything not listed above
+        _ => 4,
+    }
+}
+
+/// Number of channels in `format`, if known.
+pub fn format_component_count(format: RenderFormat) -> Option<u32> {
+    match format {
+        RenderFormat::R32g32b32a32Float
+        | RenderFormat::R16g16b16a16Float
+        | RenderFormat::R10g10b10a2Unorm
+        | RenderFormat::R8g8b8a8Unorm => Some(4),
+        RenderFormat::R32g32b32Float => Some(3),
+        RenderFormat::R32g32Float | RenderFormat::R16g16Float => Some(2),
+        RenderFormat::R32Float
+        | RenderFormat::D32Float
+        | RenderFormat::R16Float
+        | RenderFormat::R8Unorm => Some(1),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct BufferDesc {
+    pub size: usize,
+    /// Size of one element of a structured buffer, or zero for raw (byte address) buffers
+    pub stride: u32,
+    /// Bind flags needed on top of the shader resource and unordered access ones
+    /// which all graph buffers get, e.g. `RenderBindFlags::INDIRECT_BUFFER`
+    pub usage: RenderBindFlags,
+}
+
+impl ResourceDesc for BufferDesc {
+    type Resource = Buffer;
+
+    fn subresource_counts(&self) -> (u32, u32) {
+        (1, 1)
+    }
+}
+
+impl BufferDesc {
+    pub fn structured(element_count: usize, stride: u32) -> Self {
+        Self {
+            size: element_count * stride as usize,
+            stride,
+            usage: RenderBindFlags::NONE,
+        }
+    }
+
+    pub fn raw(size: usize) -> Self {
+        Self {
+            size,
+            stride: 0,
+            usage: RenderBindFlags::NONE,
+        }
+    }
+
+    pub fn usage(mut self, usage: RenderBindFlags) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Number of elements views of the buffer cover. Raw buffers are viewed as 32-bit words.
+    pub fn element_count(&self) -> u32 {
+        if self.stride == 0 {
+            (self.size / 4) as u32
+        } else {
+            (self.size / self.stride as usize) as u32
+        }
+    }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub(crate) struct GraphRawResourceHandle {
     pub(crate) id: u32,
     pub(crate) version: u32,
@@ -69,10 +343,29 @@ impl<ResType: Resource> Handle<ResType> {
 
 impl<ResType: Resource> Eq for Handle<ResType> {}
 
+/// A resource which outlives the graph; see `RenderGraph::export`.
+#[derive(Debug)]
+pub struct ExportedHandle<ResType: Resource> {
+    pub(crate) raw: GraphRawResourceHandle,
+    pub(crate) marker: PhantomData<ResType>,
+}
+
+impl<ResType: Resource> Clone for ExportedHandle<ResType> {
+    fn clone(&self) -> Self {
+        Self {
+            raw: self.raw,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<ResType: Resource> Copy for ExportedHandle<ResType> {}
+
 #[derive(Debug)]
 pub struct Ref<ResType: Resource, AccessMode> {
     pub(crate) handle: GraphRawResourceHandle,
     pub(crate) desc: <ResType as Resource>::Desc,
+    pub(crate) subresources: SubresourceRange,
     pub(crate) marker: PhantomData<(ResType, AccessMode)>,
 }
 
@@ -80,6 +373,11 @@ impl<ResType: Resource, AccessMode> Ref<ResType, AccessMode> {
     pub fn desc(&self) -> &<ResType as Resource>::Desc {
         &self.desc
     }
+
+    /// The part of the resource accessible through this reference.
+    pub fn subresources(&self) -> SubresourceRange {
+        self.subresources
+    }
 }
 
 impl<ResType: Resource, AccessMode> Clone for Ref<ResType, AccessMode>
@@ -91,6 +389,7 @@ where
         Self {
             handle: self.handle.clone(),
             desc: self.desc.clone(),
+            subresources: self.subresources,
             marker: PhantomData,
         }
     }
@@ -111,6 +410,29 @@ where
         Ref {
             handle: self.handle,
             desc: self.desc,
+            subresources: self.subresources,
+            marker: PhantomData,
+        }
+    }
+
+    /// Narrows the reference down to a single mip level within its range.
+    pub(crate) fn mip(&self, mip: u32) -> Ref<ResType, AccessMode> {
+        assert!(
+            mip >= self.subresources.base_mip
+                && mip < self.subresources.base_mip + self.subresources.mip_count,
+            "Mip {} is not within {:?}",
+            mip,
+            self.subresources
+        );
+
+        Ref {
+            handle: self.handle,
+            desc: self.desc,
+            subresources: SubresourceRange {
+                base_mip: mip,
+                mip_count: 1,
+                ..self.subresources
+            },
             marker: PhantomData,
         }
     }
@@ -120,6 +442,8 @@ where
 pub struct GpuSrv(pub RenderResourceHandle);
 pub struct GpuUav(pub RenderResourceHandle);
 pub struct GpuRt(pub RenderResourceHandle);
+pub struct GpuDsv(pub RenderResourceHandle);
+pub struct GpuDsvReadOnly(pub RenderResourceHandle);
 
 pub trait ToGpuResourceView {
     fn to_gpu_resource_view(res: RenderResourceHandle) -> Self;
@@ -142,3 +466,15 @@ impl ToGpuResourceView for GpuRt {
         Self(res)
     }
 }
+
+impl ToGpuResourceView for GpuDsv {
+    fn to_gpu_resource_view(res: RenderResourceHandle) -> Self {
+        Self(res)
+    }
+}
+
+impl ToGpuResourceView for GpuDsvReadOnly {
+    fn to_gpu_resource_view(res: RenderResourceHandle) -> Self {
+        Self(res)
+    }
+}
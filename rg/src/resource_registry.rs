@@ -1,13 +1,17 @@
 use crate::{
     graph::RenderGraphExecutionParams,
-    pipeline::{ComputePipeline, RasterPipeline, RasterPipelineDesc},
+    pipeline::{
+        ComputePipeline, RasterPipeline, RasterPipelineDesc, RayTracingPipeline,
+        RayTracingPipelineDesc,
+    },
     resource::*,
     DynamicConstants, RenderTarget,
 };
 
 use render_core::{
     constants::MAX_RENDER_TARGET_COUNT,
-    state::{RenderBindingRenderTargetView, RenderBindingView},
+    encoder::RenderCommandList,
+    state::{RenderBindingDepthStencilView, RenderBindingRenderTargetView, RenderBindingView},
     types::*,
 };
 use std::{path::Path, sync::Arc};
@@ -16,12 +20,21 @@ pub struct ResourceRegistry<'exec_params, 'device, 'pipeline_cache, 'res_alloc,
     pub execution_params:
         &'exec_params RenderGraphExecutionParams<'device, 'pipeline_cache, 'res_alloc>,
     pub(crate) resources: Vec<RenderResourceHandle>,
-    pub dynamic_constants: &'constants mut DynamicConstants,
+    pub dynamic_constants: &'constants DynamicConstants,
+    pub(crate) current_pass_name: String,
+    // Scopes opened by the current pass, closed for it if it fails
+    pub(crate) open_render_pass: bool,
+    pub(crate) open_events: u32,
 }
 
 impl<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
     ResourceRegistry<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
 {
+    /// Name of the pass currently being recorded; useful for debug labels.
+    pub fn current_pass_name(&self) -> &str {
+        &self.current_pass_name
+    }
+
     pub fn resource<T: Resource, GpuResType>(&self, resource: Ref<T, GpuResType>) -> GpuResType
     where
         GpuResType: ToGpuResourceView,
@@ -41,6 +54,15 @@ impl<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
             .get_or_load_compute(self.execution_params, shader_path.as_ref())
     }
 
+    pub fn ray_tracing_pipeline(
+        &self,
+        desc: RayTracingPipelineDesc,
+    ) -> anyhow::Result<Arc<RayTracingPipeline>> {
+        self.execution_params
+            .pipeline_cache
+            .get_or_load_ray_tracing(self.execution_params, desc)
+    }
+
     pub fn render_pass(
         &self,
         render_target: &RenderTarget,
@@ -55,28 +77,45 @@ impl<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
         let mut render_target_views = [None; MAX_RENDER_TARGET_COUNT];
         for (i, rt) in render_target.color.iter().enumerate() {
             if let Some(rt) = rt {
+                let subresources = rt.texture.subresources();
                 render_target_views[i] = Some(RenderBindingRenderTargetView {
                     base: RenderBindingView {
                         resource: self.resources[rt.texture.handle.id as usize],
                         format: rt.texture.desc().format,
-                        dimension: RenderViewDimension::Tex2d,
+                        dimension: tex2d_view_dimension(rt.texture.desc()),
                     },
-                    mip_slice: 0,
-                    first_array_slice: 0,
+                    mip_slice: subresources.base_mip,
+                    first_array_slice: subresources.base_slice,
                     plane_slice_first_w_slice: 0,
-                    array_size: 0,
+                    array_size: subresources.slice_count,
                     w_size: 0,
                 });
             }
         }
 
+        let depth_stencil_view =
+            render_target
+                .depth
+                .as_ref()
+                .map(|depth| RenderBindingDepthStencilView {
+                    base: RenderBindingView {
+                        resource: self.resources[depth.handle.id as usize],
+                        format: depth.desc.format,
+                        dimension: tex2d_view_dimension(&depth.desc),
+                    },
+                    mip_slice: depth.subresources.base_mip,
+                    first_array_slice: depth.subresources.base_slice,
+                    array_size: depth.subresources.slice_count,
+                    read_only: depth.read_only,
+                });
+
         device.create_frame_binding_set(
             frame_binding_set_handle,
             &RenderFrameBindingSetDesc {
                 render_target_views,
-                depth_stencil_view: None,
+                depth_stencil_view,
             },
-            "draw binding set".into(),
+            format!("{} binding set", self.current_pass_name).into(),
         )?;
 
         let render_pass_handle = self
@@ -88,25 +127,102 @@ impl<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
             render_pass_handle,
             &RenderPassDesc {
                 frame_binding: frame_binding_set_handle,
-                // TODO
-                render_target_info: [RenderTargetInfo {
-                    load_op: RenderLoadOp::Discard,
-                    store_op: RenderStoreOp::Store,
-                    clear_color: [0.0f32; 4],
-                }; MAX_RENDER_TARGET_COUNT],
-                depth_stencil_target_info: DepthStencilTargetInfo {
-                    load_op: RenderLoadOp::Discard,
-                    store_op: RenderStoreOp::Discard,
-                    clear_depth: 1.0,
-                    clear_stencil: 0,
-                },
+                render_target_info: array_init::array_init(|i| {
+                    render_target.color[i]
+                        .as_ref()
+                        .map_or(RenderTargetInfo::default(), |color| color.info)
+                }),
+                depth_stencil_target_info: render_target.depth.as_ref().map_or(
+                    DepthStencilTargetInfo {
+                        load_op: RenderLoadOp::Discard,
+                        store_op: RenderStoreOp::Discard,
+                        clear_depth: 1.0,
+                        clear_stencil: 0,
+                    },
+                    |depth| depth.info,
+                ),
             },
-            "render pass".into(),
+            self.current_pass_name.clone().into(),
         )?;
 
         Ok(render_pass_handle)
     }
 
+    /// Creates a render pass for `render_target`, and begins it. Unlike beginning it
+    /// directly on the command list, this lets the graph end the render pass
+    /// if the render function fails before calling `end_render_pass`.
+    pub fn begin_render_pass(
+        &mut self,
+        cb: &mut RenderCommandList<'_>,
+        render_target: &RenderTarget,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.open_render_pass,
+            "{}: a render pass is already open",
+            self.current_pass_name
+        );
+
+        cb.begin_render_pass(self.render_pass(render_target)?)?;
+        self.open_render_pass = true;
+        Ok(())
+    }
+
+    pub fn end_render_pass(&mut self, cb: &mut RenderCommandList<'_>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.open_render_pass,
+            "{}: no render pass is open",
+            self.current_pass_name
+        );
+
+        cb.end_render_pass()?;
+        self.open_render_pass = false;
+        Ok(())
+    }
+
+    /// Begins a debug event nested within the pass's own. Ended by the graph
+    /// if the render function fails before calling `end_event`.
+    pub fn begin_event(
+        &mut self,
+        cb: &mut RenderCommandList<'_>,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        cb.begin_event(name)?;
+        self.open_events += 1;
+        Ok(())
+    }
+
+    pub fn end_event(&mut self, cb: &mut RenderCommandList<'_>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.open_events > 0,
+            "{}: no debug event is open",
+            self.current_pass_name
+        );
+
+        cb.end_event()?;
+        self.open_events -= 1;
+        Ok(())
+    }
+
+    /// Ends the render pass and debug events left open by the current pass, innermost first.
+    /// Returns whether there were any.
+    pub(crate) fn close_open_scopes(
+        &mut self,
+        cb: &mut RenderCommandList<'_>,
+    ) -> anyhow::Result<bool> {
+        let any_open = self.open_render_pass || self.open_events > 0;
+
+        if self.open_render_pass {
+            cb.end_render_pass()?;
+            self.open_render_pass = false;
+        }
+        while self.open_events > 0 {
+            cb.end_event()?;
+            self.open_events -= 1;
+        }
+
+        Ok(any_open)
+    }
+
     pub fn raster_pipeline(
         &self,
         desc: RasterPipelineDesc,
@@ -119,3 +235,12 @@ impl<'exec_params, 'device, 'pipeline_cache, 'res_alloc, 'constants>
         )
     }
 }
+
+// Array views are needed to access slices other than the first one.
+fn tex2d_view_dimension(desc: &TextureDesc) -> RenderViewDimension {
+    if desc.array_elements > 1 {
+        RenderViewDimension::Tex2dArray
+    } else {
+        RenderViewDimension::Tex2d
+    }
+}
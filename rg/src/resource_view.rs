@@ -1,4 +1,7 @@
-use crate::{pipeline::ComputePipeline, resource_registry::ResourceRegistry};
+use crate::{
+    pipeline::{ComputePipeline, RasterPipeline},
+    resource_registry::ResourceRegistry,
+};
 use render_core::{handles::*, state::build, types::*};
 use std::sync::Arc;
 
@@ -7,28 +10,46 @@ pub use render_core::types::{RenderShaderArgument, RenderShaderType};
 pub mod srv {
     use crate::resource::*;
 
-    pub struct RgSrv {
-        // TODO: non-texture
-        pub rg_ref: Ref<Texture, GpuSrv>,
+    pub enum RgSrv {
+        Texture2d(Ref<Texture, GpuSrv>),
+        Buffer(Ref<Buffer, GpuSrv>),
     }
 
+    /// Views all the mips the reference covers.
     pub fn texture_2d(rg_ref: Ref<Texture, GpuSrv>) -> RgSrv {
-        RgSrv {
-            rg_ref: rg_ref.internal_clone(),
-        }
+        RgSrv::Texture2d(rg_ref.internal_clone())
+    }
+
+    /// Views one mip of those the reference covers.
+    pub fn texture_2d_mip(rg_ref: Ref<Texture, GpuSrv>, mip: u32) -> RgSrv {
+        RgSrv::Texture2d(rg_ref.mip(mip))
+    }
+
+    pub fn buffer(rg_ref: Ref<Buffer, GpuSrv>) -> RgSrv {
+        RgSrv::Buffer(rg_ref.internal_clone())
     }
 }
 
 pub mod uav {
     use crate::resource::*;
 
-    pub struct RgUav {
-        // TODO: non-texture
-        pub rg_ref: Ref<Texture, GpuUav>,
+    pub enum RgUav {
+        Texture2d(Ref<Texture, GpuUav>),
+        Buffer(Ref<Buffer, GpuUav>),
     }
 
+    /// Views the first mip the reference covers.
     pub fn texture_2d(rg_ref: Ref<Texture, GpuUav>) -> RgUav {
-        RgUav { rg_ref }
+        RgUav::Texture2d(rg_ref)
+    }
+
+    /// Views one mip of those the reference covers.
+    pub fn texture_2d_mip(rg_ref: Ref<Texture, GpuUav>, mip: u32) -> RgUav {
+        RgUav::Texture2d(rg_ref.mip(mip))
+    }
+
+    pub fn buffer(rg_ref: Ref<Buffer, GpuUav>) -> RgUav {
+        RgUav::Buffer(rg_ref)
     }
 }
 
@@ -38,7 +59,7 @@ pub trait NamedShaderViews {
         registry: &ResourceRegistry,
         srvs: &[(&'static str, srv::RgSrv)],
         uavs: &[(&'static str, uav::RgUav)],
-    ) -> RenderResourceHandle;
+    ) -> anyhow::Result<RenderResourceHandle>;
 }
 
 impl NamedShaderViews for Arc<ComputePipeline> {
@@ -47,63 +68,133 @@ impl NamedShaderViews for Arc<ComputePipeline> {
         registry: &ResourceRegistry,
         srvs: &[(&'static str, srv::RgSrv)],
         uavs: &[(&'static str, uav::RgUav)],
-    ) -> RenderResourceHandle {
-        let mut resource_views = RenderShaderViewsDesc {
-            shader_resource_views: vec![Default::default(); srvs.len()],
-            unordered_access_views: vec![Default::default(); uavs.len()],
-        };
+    ) -> anyhow::Result<RenderResourceHandle> {
+        create_named_views(&self.srvs, &self.uavs, registry, srvs, uavs)
+    }
+}
 
-        for (srv_name, srv) in srvs.into_iter() {
-            let binding_idx = self
-                .srvs
-                .iter()
-                .position(|name| name == srv_name)
-                .expect(srv_name);
-
-            // TODO: other binding types
-            resource_views.shader_resource_views[binding_idx] = build::texture_2d(
-                registry.resource(srv.rg_ref.internal_clone()).0,
-                srv.rg_ref.desc().format,
-                0,
-                1,
-                0,
-                0.0f32,
-            );
-        }
-
-        for (uav_name, uav) in uavs.into_iter() {
-            let binding_idx = self
-                .uavs
-                .iter()
-                .position(|name| name == uav_name)
-                .expect(uav_name);
-
-            // TODO: other binding types
-            resource_views.unordered_access_views[binding_idx] = build::texture_2d_rw(
-                registry.resource(uav.rg_ref.internal_clone()).0,
-                uav.rg_ref.desc().format,
+impl NamedShaderViews for Arc<RasterPipeline> {
+    fn named_views(
+        &self,
+        registry: &ResourceRegistry,
+        srvs: &[(&'static str, srv::RgSrv)],
+        uavs: &[(&'static str, uav::RgUav)],
+    ) -> anyhow::Result<RenderResourceHandle> {
+        create_named_views(&self.srvs, &self.uavs, registry, srvs, uavs)
+    }
+}
+
+fn create_named_views(
+    srv_names: &[String],
+    uav_names: &[String],
+    registry: &ResourceRegistry,
+    srvs: &[(&'static str, srv::RgSrv)],
+    uavs: &[(&'static str, uav::RgUav)],
+) -> anyhow::Result<RenderResourceHandle> {
+    // Views go at the positions of their bindings in the shader
+    let mut resource_views = RenderShaderViewsDesc {
+        shader_resource_views: vec![Default::default(); srv_names.len()],
+        unordered_access_views: vec![Default::default(); uav_names.len()],
+    };
+    let mut srv_bound = vec![false; srv_names.len()];
+    let mut uav_bound = vec![false; uav_names.len()];
+
+    for (srv_name, srv) in srvs.iter() {
+        let binding_idx = srv_names
+            .iter()
+            .position(|name| name == srv_name)
+            .ok_or_else(|| anyhow::anyhow!("The shader has no SRV named {:?}", srv_name))?;
+        srv_bound[binding_idx] = true;
+
+        resource_views.shader_resource_views[binding_idx] = match srv {
+            srv::RgSrv::Texture2d(rg_ref) => {
+                let subresources = rg_ref.subresources();
+                let resource = registry.resource(rg_ref.internal_clone()).0;
+
+                if rg_ref.desc().array_elements > 1 {
+                    build::texture_2d_array(
+                        resource,
+                        rg_ref.desc().format,
+                        subresources.base_mip,
+                        subresources.mip_count,
+                        subresources.base_slice,
+                        subresources.slice_count,
+                        0,
+                        0.0f32,
+                    )
+                } else {
+                    build::texture_2d(
+                        resource,
+                        rg_ref.desc().format,
+                        subresources.base_mip,
+                        subresources.mip_count,
+                        0,
+                        0.0f32,
+                    )
+                }
+            }
+            srv::RgSrv::Buffer(rg_ref) => build::buffer(
+                registry.resource(rg_ref.internal_clone()).0,
+                RenderFormat::Unknown,
                 0,
+                rg_ref.desc().element_count(),
+                rg_ref.desc().stride,
+            ),
+        };
+    }
+
+    for (uav_name, uav) in uavs.iter() {
+        let binding_idx = uav_names
+            .iter()
+            .position(|name| name == uav_name)
+            .ok_or_else(|| anyhow::anyhow!("The shader has no UAV named {:?}", uav_name))?;
+        uav_bound[binding_idx] = true;
+
+        resource_views.unordered_access_views[binding_idx] = match uav {
+            uav::RgUav::Texture2d(rg_ref) => {
+                let subresources = rg_ref.subresources();
+                let resource = registry.resource(rg_ref.internal_clone()).0;
+
+                if rg_ref.desc().array_elements > 1 {
+                    build::texture_2d_array_rw(
+                        resource,
+                        rg_ref.desc().format,
+                        subresources.base_mip,
+                        subresources.base_slice,
+                        subresources.slice_count,
+                        0,
+                    )
+                } else {
+                    build::texture_2d_rw(resource, rg_ref.desc().format, subresources.base_mip, 0)
+                }
+            }
+            uav::RgUav::Buffer(rg_ref) => build::buffer_rw(
+                registry.resource(rg_ref.internal_clone()).0,
+                RenderFormat::Unknown,
                 0,
-            );
-        }
-
-        // TODO: verify that all entries have been written to
-
-        let resource_views_handle = registry
-            .execution_params
-            .handles
-            .allocate_transient(RenderResourceType::ShaderViews);
-
-        registry
-            .execution_params
-            .device
-            .create_shader_views(
-                resource_views_handle,
-                &resource_views,
-                "shader resource views".into(),
-            )
-            .unwrap();
-
-        resource_views_handle
+                rg_ref.desc().element_count(),
+                rg_ref.desc().stride,
+            ),
+        };
+    }
+
+    if let Some(unbound) = srv_bound.iter().position(|bound| !bound) {
+        anyhow::bail!("No view provided for the SRV {:?}", srv_names[unbound]);
+    }
+    if let Some(unbound) = uav_bound.iter().position(|bound| !bound) {
+        anyhow::bail!("No view provided for the UAV {:?}", uav_names[unbound]);
     }
+
+    let resource_views_handle = registry
+        .execution_params
+        .handles
+        .allocate_transient(RenderResourceType::ShaderViews);
+
+    registry.execution_params.device.create_shader_views(
+        resource_views_handle,
+        &resource_views,
+        format!("{} views", registry.current_pass_name()).into(),
+    )?;
+
+    Ok(resource_views_handle)
 }
@@ -6,25 +6,48 @@ pub struct ComputeShaderCacheEntry {
     pub shader_handle: RenderResourceHandle,
     pub srvs: Vec<String>,
     pub uavs: Vec<String>,
+    pub cbuffers: Vec<String>,
     pub group_size: [u32; 3],
 }
 
+/// A vertex shader input fed from vertex buffers
+pub struct ShaderVertexInput {
+    /// E.g. "POSITION" or "TEXCOORD1"
+    pub semantic: String,
+    pub location: u32,
+    /// 1 for scalars, otherwise the vector size
+    pub component_count: u32,
+}
+
 pub struct RasterShaderCacheEntry {
     pub shader_handle: RenderResourceHandle,
     pub stage: RenderShaderType,
+    pub srvs: Vec<String>,
+    pub uavs: Vec<String>,
+    pub cbuffers: Vec<String>,
+    /// Only present for vertex shaders
+    pub vertex_inputs: Vec<ShaderVertexInput>,
+}
+
+pub struct RayTracingShaderCacheEntry {
+    /// A `RayTracingProgram` resource
+    pub shader_handle: RenderResourceHandle,
+    pub program_type: RayTracingProgramType,
 }
 
 // TODO: figure out the ownership model -- should this release the resources?
 pub enum ShaderCacheEntry {
     Compute(ComputeShaderCacheEntry),
     Raster(RasterShaderCacheEntry),
+    RayTracing(RayTracingShaderCacheEntry),
 }
 
 impl ShaderCacheEntry {
     pub fn shader_handle(&self) -> RenderResourceHandle {
         match self {
             Self::Compute(ComputeShaderCacheEntry { shader_handle, .. })
-            | Self::Raster(RasterShaderCacheEntry { shader_handle, .. }) => *shader_handle,
+            | Self::Raster(RasterShaderCacheEntry { shader_handle, .. })
+            | Self::RayTracing(RayTracingShaderCacheEntry { shader_handle, .. }) => *shader_handle,
         }
     }
 }
@@ -46,11 +69,19 @@ pub struct ShaderCacheOutput {
     pub retired: Option<Arc<ShaderCacheEntry>>,
 }
 
-pub trait ShaderCache {
+pub trait ShaderCache: Send + Sync {
     fn get_or_load(
         &self,
         params: &RenderGraphExecutionParams<'_, '_, '_>,
         shader_type: RenderShaderType,
         path: &Path,
     ) -> ShaderCacheOutput;
+
+    /// Like `get_or_load`, but creates a ray tracing program of the given type.
+    fn get_or_load_ray_tracing(
+        &self,
+        params: &RenderGraphExecutionParams<'_, '_, '_>,
+        program_type: RayTracingProgramType,
+        path: &Path,
+    ) -> ShaderCacheOutput;
 }
@@ -0,0 +1,127 @@
+use crate::graph::{GpuResourceCreator, GraphResourceDesc};
+use render_core::{
+    handles::RenderResourceHandle,
+    types::{RenderBindFlags, RenderResourceStates},
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy)]
+pub(crate) struct TemporalResourceInstance {
+    pub handle: RenderResourceHandle,
+    /// State the resource was left in at the end of the last graph using it;
+    /// `None` if it's just been created.
+    pub state: Option<RenderResourceStates>,
+}
+
+struct TemporalResource {
+    desc: GraphResourceDesc,
+    bind_flags: RenderBindFlags,
+    // One instance for resources updated in-place, two for ping-pong resources.
+    // For the latter, the first one is written in the current frame, and the second
+    // one holds the previous frame's data.
+    instances: Vec<TemporalResourceInstance>,
+    // Frame in which a graph last asked for the resource
+    last_requested_frame: u64,
+}
+
+/// Graph resources which persist across frames, identified by name.
+/// See `RenderGraph::get_or_create_temporal`.
+///
+/// Each graph execution counts as a frame. Resources which a frame's graph doesn't ask for
+/// are retired at the end of it; see `take_retired`.
+#[derive(Default)]
+pub struct TemporalResources {
+    resources: HashMap<String, TemporalResource>,
+    retired: Vec<RenderResourceHandle>,
+    frame: u64,
+}
+
+impl TemporalResources {
+    /// Returns the GPU resources backing the named temporal resource, creating them
+    /// if they don't exist yet, or if the desc or usage has changed since the last frame.
+    pub(crate) fn get_or_create(
+        &mut self,
+        creator: &dyn GpuResourceCreator,
+        name: &str,
+        desc: GraphResourceDesc,
+        mut bind_flags: RenderBindFlags,
+        instance_count: usize,
+    ) -> anyhow::Result<&[TemporalResourceInstance]> {
+        if let Some(existing) = self.resources.get(name) {
+            let compatible = existing.desc == desc
+                && existing.instances.len() == instance_count
+                && existing.bind_flags.contains(bind_flags);
+
+            if !compatible {
+                // Keep supporting previous usage too, so that resources used differently
+                // on alternating frames don't get recreated back and forth.
+                bind_flags |= existing.bind_flags;
+
+                let existing = self.resources.remove(name).unwrap();
+                self.retired
+                    .extend(existing.instances.iter().map(|instance| instance.handle));
+            }
+        }
+
+        if !self.resources.contains_key(name) {
+            let mut instances = Vec::with_capacity(instance_count);
+            for _ in 0..instance_count {
+                let handle = creator.create(&desc, bind_flags, name, name)?;
+                instances.push(TemporalResourceInstance {
+                    handle,
+                    state: None,
+                });
+            }
+
+            self.resources.insert(
+                name.to_owned(),
+                TemporalResource {
+                    desc,
+                    bind_flags,
+                    instances,
+                    last_requested_frame: self.frame,
+                },
+            );
+        }
+
+        let resource = self.resources.get_mut(name).unwrap();
+        resource.last_requested_frame = self.frame;
+        Ok(&resource.instances)
+    }
+
+    pub(crate) fn set_state(&mut self, name: &str, instance: usize, state: RenderResourceStates) {
+        if let Some(resource) = self.resources.get_mut(name) {
+            resource.instances[instance].state = Some(state);
+        }
+    }
+
+    /// Makes this frame's output the next frame's history.
+    pub(crate) fn swap(&mut self, name: &str) {
+        if let Some(resource) = self.resources.get_mut(name) {
+            resource.instances.rotate_left(1);
+        }
+    }
+
+    /// Retires the resources which weren't requested during the frame, and starts the next one.
+    pub(crate) fn end_frame(&mut self) {
+        let frame = self.frame;
+        let retired = &mut self.retired;
+
+        self.resources.retain(|_, resource| {
+            let requested = resource.last_requested_frame == frame;
+            if !requested {
+                retired.extend(resource.instances.iter().map(|instance| instance.handle));
+            }
+            requested
+        });
+
+        self.frame += 1;
+    }
+
+    /// Takes the resources which have been replaced since the last call, e.g. because
+    /// their desc has changed, or which the last frame's graph didn't use. They must be kept
+    /// alive until the GPU is done with the current frame, and then destroyed.
+    pub fn take_retired(&mut self) -> Vec<RenderResourceHandle> {
+        std::mem::take(&mut self.retired)
+    }
+}
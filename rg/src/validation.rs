@@ -0,0 +1,260 @@
+use crate::{
+    graph::{GraphResourceDesc, GraphResourceInfo, RenderGraph},
+    resource::SubresourceRange,
+};
+use std::fmt;
+
+/// A misuse of the render graph API, detected either while building the graph,
+/// or by `RenderGraph::validate`.
+#[derive(Clone, Debug)]
+pub enum GraphValidationError {
+    /// Overlapping parts of a resource are written more than once by one pass.
+    DuplicateWrite {
+        pass: String,
+        resource: String,
+    },
+    /// Overlapping parts of a resource are both read and written by one pass.
+    ReadWriteOverlap {
+        pass: String,
+        resource: String,
+    },
+    /// A pass accesses a resource through a handle which has since been written to.
+    /// `version` is the one accessed, and `latest` the newest one at that point.
+    StaleAccess {
+        pass: String,
+        resource: String,
+        version: u32,
+        latest: u32,
+    },
+    /// A pass reads a resource created by the graph before any pass has written to it.
+    ReadBeforeWrite {
+        pass: String,
+        resource: String,
+    },
+    SubresourcesOutOfBounds {
+        pass: String,
+        resource: String,
+        subresources: SubresourceRange,
+        mip_levels: u32,
+        slice_count: u32,
+    },
+    /// Render targets and depth buffers can only be used by graphics passes.
+    RasterOnAsyncCompute {
+        pass: String,
+        resource: String,
+    },
+    /// render-core can only create single-sampled textures. Reported for the first pass
+    /// accessing the resource.
+    Multisampled {
+        pass: String,
+        resource: String,
+        sample_count: u32,
+    },
+    MissingRenderFn {
+        pass: String,
+    },
+    DuplicateRenderFn {
+        pass: String,
+    },
+    DuplicateTemporal {
+        resource: String,
+        instance: usize,
+    },
+}
+
+impl fmt::Display for GraphValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateWrite { pass, resource } => {
+                write!(f, "pass {:?} writes to {:?} twice", pass, resource)
+            }
+            Self::ReadWriteOverlap { pass, resource } => write!(
+                f,
+                "pass {:?} both reads and writes the same part of {:?}",
+                pass, resource
+            ),
+            Self::StaleAccess {
+                pass,
+                resource,
+                version,
+                latest,
+            } => write!(
+                f,
+                "pass {:?} accesses version {} of {:?}, which is stale (latest is {})",
+                pass, version, resource, latest
+            ),
+            Self::ReadBeforeWrite { pass, resource } => write!(
+                f,
+                "pass {:?} reads {:?} before anything wrote to it",
+                pass, resource
+            ),
+            Self::SubresourcesOutOfBounds {
+                pass,
+                resource,
+                subresources,
+                mip_levels,
+                slice_count,
+            } => write!(
+                f,
+                "pass {:?} accesses {:?} of {:?}, which only has {} mips and {} slices",
+                pass, subresources, resource, mip_levels, slice_count
+            ),
+            Self::RasterOnAsyncCompute { pass, resource } => write!(
+                f,
+                "async compute pass {:?} can't use {:?} as a render target or depth buffer",
+                pass, resource
+            ),
+            Self::Multisampled {
+                pass,
+                resource,
+                sample_count,
+            } => write!(
+                f,
+                "pass {:?} uses {:?} with {} samples, but textures must be single-sampled",
+                pass, resource, sample_count
+            ),
+            Self::MissingRenderFn { pass } => {
+                write!(f, "pass {:?} has no render function", pass)
+            }
+            Self::DuplicateRenderFn { pass } => {
+                write!(f, "pass {:?} has more than one render function", pass)
+            }
+            Self::DuplicateTemporal { resource, instance } => write!(
+                f,
+                "temporal resource {:?} (instance {}) requested twice",
+                resource, instance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphValidationError {}
+
+/// All the problems found in a graph. Returned by `RenderGraph::validate`
+/// and `RenderGraph::execute` inside an `anyhow::Error`.
+#[derive(Clone, Debug)]
+pub struct GraphValidationErrors(pub Vec<GraphValidationError>);
+
+impl fmt::Display for GraphValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Render graph validation failed:")?;
+        for error in self.0.iter() {
+            write!(f, "\n    {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GraphValidationErrors {}
+
+impl RenderGraph {
+    /// Checks the graph for API misuse which would otherwise produce wrong results
+    /// or panic during execution. Problems are reported as `GraphValidationErrors`,
+    /// and resources created but never accessed by any pass are logged as warnings.
+    ///
+    /// Called by `execute`, but can be used to reject a graph earlier.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = self.build_errors.clone();
+
+        // Newest version of each resource written so far
+        let mut latest_version = vec![0u32; self.resources.len()];
+        let mut resource_accessed = vec![false; self.resources.len()];
+        let mut multisampled_reported = vec![false; self.resources.len()];
+
+        for pass in self.passes.iter() {
+            if pass.render_fn.is_none() {
+                errors.push(GraphValidationError::MissingRenderFn {
+                    pass: pass.name.clone(),
+                });
+            }
+
+            for resource_ref in pass.read.iter().chain(pass.write.iter()) {
+                let id = resource_ref.handle.id as usize;
+                let resource = &self.resources[id];
+
+                // Imported textures already exist, so only the ones the graph creates count.
+                let sample_count = match (resource, resource.desc()) {
+                    (GraphResourceInfo::Imported(..), _) => 1,
+                    (_, GraphResourceDesc::Texture(desc)) => desc.sample_count,
+                    (_, GraphResourceDesc::Buffer(..)) => 1,
+                };
+
+                if sample_count != 1 && !multisampled_reported[id] {
+                    multisampled_reported[id] = true;
+                    errors.push(GraphValidationError::Multisampled {
+                        pass: pass.name.clone(),
+                        resource: resource.name().to_owned(),
+                        sample_count,
+                    });
+                }
+            }
+
+            for resource_ref in pass.read.iter() {
+                let id = resource_ref.handle.id as usize;
+                let version = resource_ref.handle.version;
+                resource_accessed[id] = true;
+
+                if version < latest_version[id] {
+                    errors.push(GraphValidationError::StaleAccess {
+                        pass: pass.name.clone(),
+                        resource: self.resources[id].name().to_owned(),
+                        version,
+                        latest: latest_version[id],
+                    });
+                } else if version == 0 {
+                    if let GraphResourceInfo::Created(info) = &self.resources[id] {
+                        errors.push(GraphValidationError::ReadBeforeWrite {
+                            pass: pass.name.clone(),
+                            resource: info.name.clone(),
+                        });
+                    }
+                }
+            }
+
+            // Each write bumps the version of the handle it goes through. Writes of a pass
+            // to disjoint subresources produce consecutive versions.
+            let mut writes: Vec<_> = pass.write.iter().map(|r| r.handle).collect();
+            writes.sort_by_key(|handle| (handle.id, handle.version));
+
+            for handle in writes {
+                let id = handle.id as usize;
+                resource_accessed[id] = true;
+
+                if handle.version != latest_version[id] + 1 {
+                    errors.push(GraphValidationError::StaleAccess {
+                        pass: pass.name.clone(),
+                        resource: self.resources[id].name().to_owned(),
+                        version: handle.version - 1,
+                        latest: latest_version[id],
+                    });
+                }
+
+                latest_version[id] = latest_version[id].max(handle.version);
+            }
+        }
+
+        for exported in self.exported_resources.iter() {
+            resource_accessed[exported.handle.id as usize] = true;
+        }
+
+        for (resource, accessed) in self.resources.iter().zip(resource_accessed.iter()) {
+            if let GraphResourceInfo::Created(info) = resource {
+                if !accessed {
+                    log::warn!(
+                        "Resource {:?} created by pass {:?} is never used",
+                        info.name,
+                        self.passes
+                            .get(info.create_pass_idx)
+                            .map_or("", |pass| pass.name.as_str())
+                    );
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(GraphValidationErrors(errors).into())
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use render_core::types::{RenderFormat, RenderResourceStates};
+use rg::*;
+
+struct Brighten {
+    enabled: bool,
+}
+
+impl RenderFeature for Brighten {
+    type Inputs = Handle<Texture>;
+    type Outputs = Handle<Texture>;
+
+    fn name(&self) -> &str {
+        "brighten"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn add_passes(&self, rg: &mut RenderGraph, input: Handle<Texture>) -> Handle<Texture> {
+        let mut pass = rg.add_pass("apply");
+        pass.read(&input);
+
+        let mut output = pass.create("output", input.desc());
+        pass.write(&mut output);
+        pass.render(|_, _| Ok(()));
+
+        output
+    }
+
+    fn bypass(&self, input: Handle<Texture>) -> Handle<Texture> {
+        input
+    }
+}
+
+fn synth(rg: &mut RenderGraph) -> Handle<Texture> {
+    let mut pass = rg.add_pass("synth");
+    let mut output = pass.create(
+        "synth",
+        &TextureDesc::new_2d(64, 64, RenderFormat::R16g16b16a16Float),
+    );
+    pass.write(&mut output);
+    pass.render(|_, _| Ok(()));
+
+    output
+}
+
+// Names of passes which would be recorded, and of the culled ones.
+fn recorded_and_culled(rg: &RenderGraph) -> (Vec<String>, Vec<String>) {
+    let mut recorded = Vec::new();
+    let mut culled = Vec::new();
+
+    for event in rg.trace().unwrap().events {
+        match event {
+            TraceEvent::Pass { name } => recorded.push(name),
+            TraceEvent::CullPass { name } => culled.push(name),
+            _ => {}
+        }
+    }
+
+    (recorded, culled)
+}
+
+#[test]
+fn disabled_feature_adds_no_passes() {
+    let mut rg = RenderGraph::new();
+    let tex = synth(&mut rg);
+    let tex = rg.add_feature(&Brighten { enabled: false }, tex);
+    rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+    assert_eq!(
+        recorded_and_culled(&rg),
+        (vec!["synth".to_owned()], Vec::new())
+    );
+}
+
+#[test]
+fn enabled_feature_contributing_to_exports_is_kept() {
+    let mut rg = RenderGraph::new();
+    let tex = synth(&mut rg);
+    let tex = rg.add_feature(&Brighten { enabled: true }, tex);
+    rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+    assert_eq!(
+        recorded_and_culled(&rg),
+        (
+            vec!["synth".to_owned(), "brighten/apply".to_owned()],
+            Vec::new()
+        )
+    );
+}
+
+#[test]
+fn feature_with_unused_output_is_culled() {
+    let mut rg = RenderGraph::new();
+    let tex = synth(&mut rg);
+    let _unused = rg.add_feature(&Brighten { enabled: true }, tex.clone());
+    rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+    assert_eq!(
+        recorded_and_culled(&rg),
+        (vec!["synth".to_owned()], vec!["brighten/apply".to_owned()])
+    );
+}
+
+#[test]
+fn never_cull_keeps_passes_without_consumers() {
+    let mut rg = RenderGraph::new();
+    let tex = synth(&mut rg);
+
+    let mut pass = rg.add_pass("readback");
+    pass.read(&tex);
+    pass.never_cull();
+    pass.render(|_, _| Ok(()));
+
+    assert_eq!(
+        recorded_and_culled(&rg),
+        (vec!["synth".to_owned(), "readback".to_owned()], Vec::new())
+    );
+}
@@ -0,0 +1,114 @@
+use render_core::types::{RenderFormat, RenderResourceStates};
+use rg::*;
+
+// "tex" is written by "synth" and "modify", and exported. "unused" reads it on the async
+// compute queue, but writes nothing the exports depend on, so it gets culled.
+fn small_graph() -> RenderGraph {
+    let mut rg = RenderGraph::new();
+    let desc = TextureDesc::new_2d(64, 32, RenderFormat::R16g16b16a16Float);
+
+    let mut tex = {
+        let mut pass = rg.add_pass("synth");
+        let mut tex = pass.create("tex", &desc);
+        pass.write(&mut tex);
+        pass.render(|_, _| Ok(()));
+        tex
+    };
+
+    {
+        let mut pass = rg.add_async_compute_pass("unused");
+        pass.read(&tex);
+        let mut scratch = pass.create("scratch", &desc);
+        pass.write(&mut scratch);
+        pass.render(|_, _| Ok(()));
+    }
+
+    {
+        let mut pass = rg.add_pass("modify");
+        pass.write(&mut tex);
+        pass.render(|_, _| Ok(()));
+    }
+
+    rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+    rg
+}
+
+#[test]
+fn dot_snapshot() {
+    let expected = r#"digraph rg {
+    rankdir=LR;
+    r0_v1 [shape=ellipse, label="tex v1\nTex2d 64x32x1 R16g16b16a16Float, 1 mips, 1 elements\npasses 0..=2"];
+    r0_v2 [shape=ellipse, label="tex v2\nTex2d 64x32x1 R16g16b16a16Float, 1 mips, 1 elements\npasses 0..=2"];
+    r1_v1 [shape=ellipse, label="scratch v1\nTex2d 64x32x1 R16g16b16a16Float, 1 mips, 1 elements\nunused"];
+    p0 [shape=box, style=solid, label="synth"];
+    p0 -> r0_v1 [label="UNORDERED_ACCESS"];
+    p1 [shape=box, style=dashed, label="unused\n(async compute)"];
+    r0_v1 -> p1 [label="NON_PIXEL_SHADER_RESOURCE"];
+    p1 -> r1_v1 [label="UNORDERED_ACCESS"];
+    p2 [shape=box, style=solid, label="modify"];
+    p2 -> r0_v2 [label="UNORDERED_ACCESS"];
+    r0_v2 -> exported [label="PIXEL_SHADER_RESOURCE"];
+}
+"#;
+
+    assert_eq!(small_graph().to_dot(), expected);
+}
+
+#[test]
+fn json_snapshot() {
+    let desc = concat!(
+        r#"{"type":"texture","texture_type":"Tex2d","width":64,"height":32,"depth":1,"#,
+        r#""mip_levels":1,"array_elements":1,"sample_count":1,"format":"R16g16b16a16Float"}"#
+    );
+    let whole = r#""subresources":{"base_mip":0,"mip_count":1,"base_slice":0,"slice_count":1}"#;
+
+    let expected = [
+        r#"{"passes":["#,
+        r#"{"index":0,"name":"synth","live":true,"queue":"Graphics","read":[],"write":["#,
+        r#"{"resource":0,"version":1,"state":"UNORDERED_ACCESS","#,
+        whole,
+        r#"}]},"#,
+        r#"{"index":1,"name":"unused","live":false,"queue":"AsyncCompute","read":["#,
+        r#"{"resource":0,"version":1,"state":"NON_PIXEL_SHADER_RESOURCE","#,
+        whole,
+        r#"}],"write":["#,
+        r#"{"resource":1,"version":1,"state":"UNORDERED_ACCESS","#,
+        whole,
+        r#"}]},"#,
+        r#"{"index":2,"name":"modify","live":true,"queue":"Graphics","read":[],"write":["#,
+        r#"{"resource":0,"version":2,"state":"UNORDERED_ACCESS","#,
+        whole,
+        r#"}]}"#,
+        r#"],"resources":["#,
+        r#"{"id":0,"name":"tex","kind":"created","desc":"#,
+        desc,
+        r#","lifetime":{"first_access":0,"last_access":2},"#,
+        r#""exported_state":"PIXEL_SHADER_RESOURCE"},"#,
+        r#"{"id":1,"name":"scratch","kind":"created","desc":"#,
+        desc,
+        r#","lifetime":null,"exported_state":null}"#,
+        r#"]}"#,
+    ]
+    .concat();
+
+    assert_eq!(small_graph().to_json(), expected);
+}
+
+#[test]
+fn dot_labels_escape_names() {
+    let mut rg = RenderGraph::new();
+    {
+        let mut pass = rg.add_pass(r#"say "hi""#);
+        let mut tex = pass.create(
+            r"C:\tex",
+            &TextureDesc::new_2d(64, 32, RenderFormat::R16g16b16a16Float),
+        );
+        pass.write(&mut tex);
+        pass.never_cull();
+        pass.render(|_, _| Ok(()));
+    }
+
+    let dot = rg.to_dot();
+    assert!(dot.contains(r#"p0 [shape=box, style=solid, label="say \"hi\""];"#));
+    assert!(dot.contains(r#"r0_v1 [shape=ellipse, label="C:\\tex v1\n"#));
+}
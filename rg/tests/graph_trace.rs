@@ -0,0 +1,266 @@
+use render_core::types::{RenderBindFlags, RenderFormat, RenderResourceStates};
+use rg::*;
+
+fn desc() -> TextureDesc {
+    TextureDesc::new_2d(64, 32, RenderFormat::R16g16b16a16Float)
+}
+
+fn synth_and_modify() -> RenderGraph {
+    let mut rg = RenderGraph::new();
+    rg.gpu_timestamps(true);
+
+    let mut tex = {
+        let mut pass = rg.add_pass("synth");
+        let mut tex = pass.create("tex", &desc());
+        pass.write(&mut tex);
+        pass.render(|_, _| Ok(()));
+        tex
+    };
+
+    {
+        let mut pass = rg.add_pass("modify");
+        pass.write(&mut tex);
+        pass.render(|_, _| Ok(()));
+    }
+
+    rg.export(tex, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+    rg
+}
+
+fn transition(
+    resource: &str,
+    before: Option<RenderResourceStates>,
+    after: RenderResourceStates,
+) -> TraceEvent {
+    TraceEvent::Transition {
+        resource: resource.to_owned(),
+        before,
+        after,
+    }
+}
+
+fn pass(name: &str) -> TraceEvent {
+    TraceEvent::Pass {
+        name: name.to_owned(),
+    }
+}
+
+#[test]
+fn pooled_resources_carry_their_state_into_the_next_frame() {
+    let uav = RenderResourceStates::UNORDERED_ACCESS;
+    let srv = RenderResourceStates::PIXEL_SHADER_RESOURCE;
+
+    // Everything after resource creation is the same in both frames, except for
+    // the state the pooled texture starts in.
+    let frame = |first_state| {
+        vec![
+            TraceEvent::Submit {
+                queue: QueueType::Graphics,
+                wait_for: Vec::new(),
+            },
+            TraceEvent::CommandList { index: 0 },
+            transition("tex", first_state, uav),
+            pass("synth"),
+            TraceEvent::Timestamp { query: 0 },
+            TraceEvent::Timestamp { query: 1 },
+            // Write-after-write still needs a barrier
+            transition("tex", Some(uav), uav),
+            pass("modify"),
+            TraceEvent::Timestamp { query: 2 },
+            TraceEvent::Timestamp { query: 3 },
+            transition("tex", Some(uav), srv),
+        ]
+    };
+
+    let mut tracer = GraphTracer::new();
+
+    let mut expected = vec![TraceEvent::CreateResource {
+        name: "tex".to_owned(),
+        desc: desc().into(),
+        bind_flags: RenderBindFlags::UNORDERED_ACCESS | RenderBindFlags::SHADER_RESOURCE,
+    }];
+    expected.extend(frame(None));
+    assert_eq!(tracer.trace(&synth_and_modify()).unwrap().events, expected);
+
+    assert_eq!(
+        tracer.trace(&synth_and_modify()).unwrap().events,
+        frame(Some(srv))
+    );
+}
+
+fn create(name: &str, bind_flags: RenderBindFlags) -> TraceEvent {
+    TraceEvent::CreateResource {
+        name: name.to_owned(),
+        desc: desc().into(),
+        bind_flags,
+    }
+}
+
+fn submit(queue: QueueType, wait_for: &[usize], command_list: usize) -> Vec<TraceEvent> {
+    vec![
+        TraceEvent::Submit {
+            queue,
+            wait_for: wait_for.to_vec(),
+        },
+        TraceEvent::CommandList {
+            index: command_list,
+        },
+    ]
+}
+
+#[test]
+fn async_compute_between_graphics_passes() {
+    let mut rg = RenderGraph::new();
+
+    let mut color = {
+        let mut pass = rg.add_pass("raster");
+        let mut color = pass.create("color", &desc());
+        pass.raster(&mut color);
+        pass.render(|_, _| Ok(()));
+        color
+    };
+
+    let ao = {
+        let mut pass = rg.add_async_compute_pass("ssao");
+        pass.read(&color);
+        let mut ao = pass.create("ao", &desc());
+        pass.write(&mut ao);
+        pass.render(|_, _| Ok(()));
+        ao
+    };
+
+    {
+        let mut pass = rg.add_pass("composite");
+        pass.read(&ao);
+        pass.write(&mut color);
+        pass.render(|_, _| Ok(()));
+    }
+
+    rg.export(color, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+
+    let rt = RenderResourceStates::RENDER_TARGET;
+    let uav = RenderResourceStates::UNORDERED_ACCESS;
+    let compute_read = RenderResourceStates::NON_PIXEL_SHADER_RESOURCE;
+    let graphics_read = RenderResourceStates::PIXEL_SHADER_RESOURCE | compute_read;
+
+    let mut expected = vec![
+        create(
+            "color",
+            RenderBindFlags::RENDER_TARGET
+                | RenderBindFlags::SHADER_RESOURCE
+                | RenderBindFlags::UNORDERED_ACCESS,
+        ),
+        create(
+            "ao",
+            RenderBindFlags::UNORDERED_ACCESS | RenderBindFlags::SHADER_RESOURCE,
+        ),
+    ];
+
+    expected.extend(submit(QueueType::Graphics, &[], 0));
+    expected.extend(vec![
+        transition("color", None, rt),
+        pass("raster"),
+        // The compute queue can't transition out of the render target state, so this
+        // goes at the end of the graphics submission, which "ssao" waits for.
+        transition("color", Some(rt), compute_read),
+    ]);
+
+    expected.extend(submit(QueueType::AsyncCompute, &[0], 1));
+    expected.extend(vec![transition("ao", None, uav), pass("ssao")]);
+
+    expected.extend(submit(QueueType::Graphics, &[1], 2));
+    expected.extend(vec![
+        transition("ao", Some(uav), graphics_read),
+        transition("color", Some(compute_read), uav),
+        pass("composite"),
+        transition(
+            "color",
+            Some(uav),
+            RenderResourceStates::PIXEL_SHADER_RESOURCE,
+        ),
+    ]);
+
+    assert_eq!(rg.trace().unwrap().events, expected);
+}
+
+#[test]
+fn independent_async_compute_joins_the_final_submission() {
+    let mut rg = RenderGraph::new();
+
+    let color = {
+        let mut pass = rg.add_pass("synth");
+        let mut color = pass.create("color", &desc());
+        pass.write(&mut color);
+        pass.render(|_, _| Ok(()));
+        color
+    };
+
+    let particles = {
+        let mut pass = rg.add_async_compute_pass("simulate");
+        let mut particles = pass.create("particles", &desc());
+        pass.write(&mut particles);
+        pass.render(|_, _| Ok(()));
+        particles
+    };
+
+    rg.export(color, RenderResourceStates::PIXEL_SHADER_RESOURCE);
+    rg.export(particles, RenderResourceStates::NON_PIXEL_SHADER_RESOURCE);
+
+    let uav = RenderResourceStates::UNORDERED_ACCESS;
+    let flags = RenderBindFlags::UNORDERED_ACCESS | RenderBindFlags::SHADER_RESOURCE;
+
+    let mut expected = vec![create("color", flags), create("particles", flags)];
+
+    expected.extend(submit(QueueType::Graphics, &[], 0));
+    expected.extend(vec![transition("color", None, uav), pass("synth")]);
+
+    expected.extend(submit(QueueType::AsyncCompute, &[], 1));
+    expected.extend(vec![transition("particles", None, uav), pass("simulate")]);
+
+    // An empty graphics submission waits for the compute work, and transitions
+    // the exports into their final states.
+    expected.extend(submit(QueueType::Graphics, &[1], 2));
+    expected.extend(vec![
+        transition(
+            "color",
+            Some(uav),
+            RenderResourceStates::PIXEL_SHADER_RESOURCE,
+        ),
+        transition(
+            "particles",
+            Some(uav),
+            RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+        ),
+    ]);
+
+    assert_eq!(rg.trace().unwrap().events, expected);
+}
+
+#[test]
+fn temporal_resources_are_retired_after_a_frame_without_them() {
+    let accumulate = || {
+        let mut rg = RenderGraph::new();
+        let mut history = rg.get_or_create_temporal("history", desc());
+        {
+            let mut pass = rg.add_pass("accumulate");
+            pass.write(&mut history);
+            pass.render(|_, _| Ok(()));
+        }
+        rg
+    };
+
+    let created = |trace: GraphTrace| {
+        trace
+            .events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::CreateResource { .. }))
+            .count()
+    };
+
+    let mut tracer = GraphTracer::new();
+    assert_eq!(created(tracer.trace(&accumulate()).unwrap()), 1);
+    assert_eq!(created(tracer.trace(&accumulate()).unwrap()), 0);
+
+    tracer.trace(&RenderGraph::new()).unwrap();
+    assert_eq!(created(tracer.trace(&accumulate()).unwrap()), 1);
+}
@@ -7,7 +7,6 @@ mod owned_resource;
 mod render_device;
 mod render_loop;
 mod render_passes;
-mod rt_util;
 mod shader_cache;
 mod shader_compiler;
 
@@ -24,8 +23,6 @@ use turbosloth::*;
 
 #[derive(Copy, Clone)]
 pub struct RaytraceData {
-    pub pipeline_state: RenderResourceHandle,
-    pub shader_table: RenderResourceHandle,
     pub top_acceleration: RenderResourceHandle,
 }
 
@@ -125,30 +122,7 @@ fn try_main(device: &MaybeRenderDevice) -> std::result::Result<(), anyhow::Error
         "TLAS".into(),
     )?;
 
-    let rt_pipeline_state = rt_util::create_ray_tracing_pipeline_state(
-        "/assets/shaders/rt/triangle.rgen.hlsl".into(),
-        "/assets/shaders/rt/triangle.rmiss.hlsl".into(),
-        "/assets/shaders/rt/triangle.rchit.hlsl".into(),
-        &device,
-        handles.clone(),
-        lazy_cache.clone(),
-    )?;
-
-    let sbt = handles.allocate(RenderResourceType::RayTracingShaderTable);
-    device.read()?.create_ray_tracing_shader_table(
-        sbt,
-        &RayTracingShaderTableDesc {
-            pipeline_state: rt_pipeline_state,
-            raygen_entry_count: 1,
-            hit_entry_count: 1,
-            miss_entry_count: 1,
-        },
-        "sbt".into(),
-    )?;
-
     let rt_data = RaytraceData {
-        pipeline_state: rt_pipeline_state,
-        shader_table: sbt,
         top_acceleration: top_as,
     };
 
@@ -175,6 +149,16 @@ fn try_main(device: &MaybeRenderDevice) -> std::result::Result<(), anyhow::Error
 
     let mut render_loop =
         render_loop::RenderLoop::new(device.clone(), handles.clone(), *error_output_texture);
+    render_loop.track_external_memory(
+        *gpu_mesh.vertex_buffer,
+        "mesh vertex buffer",
+        gpu_mesh.vertex_buffer_bytes,
+    );
+    render_loop.track_external_memory(
+        *gpu_mesh.index_buffer,
+        "mesh index buffer",
+        gpu_mesh.index_buffer_bytes,
+    );
     let mut last_error_text = None;
 
     for _ in 0..1000 {
@@ -139,6 +139,10 @@ pub fn create_render_device(
 pub struct FrameResources {
     pub handles: Vec<RenderResourceHandle>,
     pub resources_used_fence: Option<RenderResourceHandle>,
+    // Pooled graph resources to be recycled once the frame has finished on the GPU
+    pub transient_pool_frame: Option<rg::TransientPoolFrame>,
+    // Read back into the GPU profiler once the frame has finished on the GPU
+    pub timestamp_queries: Option<rg::GpuTimestampQueries>,
 }
 
 impl FrameResources {
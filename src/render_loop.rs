@@ -3,10 +3,12 @@ use crate::{
     render_device::{FrameResources, MaybeRenderDevice},
 };
 
-use render_core::{encoder::RenderCommandList, handles::*, types::*};
-use rg::{DynamicConstants, ResourceHandleAllocator};
+use render_core::{device::RenderDevice, encoder::RenderCommandList, handles::*, types::*};
+use rg::{
+    DynamicConstants, GpuProfiler, RenderGraphCache, ResourceHandleAllocator, TemporalResources,
+};
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     sync::{Arc, RwLock},
 };
 
@@ -15,9 +17,16 @@ pub struct RenderLoop<'a> {
     persistent_resources: Vec<RenderResourceHandle>,
     retired_frames: VecDeque<Option<FrameResources>>,
     error_output_texture: RenderResourceHandle,
-    main_command_list: RenderCommandList<'a>,
+    // Command lists the render graph records into; grown on demand
+    graph_command_lists: Vec<RenderCommandList<'a>>,
     early_command_list: RenderCommandList<'a>,
     dynamic_constants: DynamicConstants,
+    temporal_resources: TemporalResources,
+    graph_cache: RenderGraphCache,
+    gpu_profiler: GpuProfiler,
+    // Sizes of persistent resources, for memory reports
+    persistent_memory: HashMap<RenderResourceHandle, rg::PersistentAllocation>,
+    memory_report: rg::MemoryReport,
     handles: Arc<RwLock<RenderResourceHandleAllocator>>,
 }
 
@@ -31,9 +40,6 @@ impl<'a> RenderLoop<'a> {
         retired_frames.push_back(None);
         retired_frames.push_back(None);
 
-        let main_command_list =
-            RenderCommandList::new(handles.clone(), 1024 * 1024 * 16, 1024 * 1024).unwrap();
-
         let early_command_list =
             RenderCommandList::new(handles.clone(), 1024 * 1024 * 16, 1024 * 1024).unwrap();
 
@@ -44,9 +50,14 @@ impl<'a> RenderLoop<'a> {
             persistent_resources: Default::default(),
             retired_frames,
             error_output_texture,
-            main_command_list,
+            graph_command_lists: Default::default(),
             early_command_list,
             dynamic_constants,
+            temporal_resources: Default::default(),
+            graph_cache: Default::default(),
+            gpu_profiler: Default::default(),
+            persistent_memory: Default::default(),
+            memory_report: Default::default(),
             handles,
         }
     }
@@ -55,64 +66,76 @@ impl<'a> RenderLoop<'a> {
         &mut self,
         swapchain: RenderResourceHandle,
         pipeline_cache: &rg::pipeline_cache::PipelineCache,
-        graph_gen_fn: impl FnOnce() -> (rg::RenderGraph, rg::Handle<rg::Texture>),
+        graph_gen_fn: impl FnOnce() -> (rg::RenderGraph, rg::ExportedHandle<rg::Texture>),
     ) -> anyhow::Result<()> {
         let device = &mut *self.device.write()?;
 
-        if let Some(frame_resources) = self.retired_frames.pop_front().unwrap() {
+        if let Some(mut frame_resources) = self.retired_frames.pop_front().unwrap() {
             if let Some(fence) = frame_resources.resources_used_fence {
                 device.wait_for_fence(fence)?;
             }
 
+            if let Some(pool_frame) = frame_resources.transient_pool_frame.take() {
+                self.graph_cache.recycle(pool_frame);
+            }
+
+            if let Some(queries) = frame_resources.timestamp_queries.take() {
+                // Not worth failing the frame over
+                if let Err(err) = self.gpu_profiler.read_back(&*device, queries) {
+                    log::warn!("Failed to read back GPU timestamps: {:?}", err);
+                }
+            }
+
             frame_resources.destroy_now(&*device);
         }
 
+        let evicted_resources = self.graph_cache.take_evicted();
+        self.persistent_resources
+            .retain(|res| !evicted_resources.contains(res));
+        for resource in evicted_resources {
+            self.persistent_memory.remove(&resource);
+            device.destroy_resource(resource)?;
+        }
+
         let mut frame_resources = FrameResources::default();
         let handle_allocator = rg::TrackingResourceHandleAllocator::new(self.handles.clone());
 
-        let command_list_handle =
-            handle_allocator.allocate_transient(RenderResourceType::CommandList);
-
-        device.create_command_list(command_list_handle, "Main command list".into())?;
-
         let early_command_list = &mut self.early_command_list;
         early_command_list.reset();
 
-        let mut main_command_list = &mut self.main_command_list;
-        main_command_list.reset();
-
         let resources_used_fence = handle_allocator.allocate_transient(RenderResourceType::Fence);
 
-        let output_texture = {
-            let (rg, tex) = (graph_gen_fn)();
-
-            // println!("Recorded {} passes", rg.passes.len());
-            let execution_output = rg.execute(
-                rg::RenderGraphExecutionParams {
-                    handles: &handle_allocator,
-                    device: &*device,
-                    pipeline_cache,
-                },
-                &mut self.dynamic_constants,
-                &mut main_command_list,
-                tex,
+        let (mut rg, tex) = (graph_gen_fn)();
+        rg.gpu_timestamps(self.gpu_profiler.enabled());
+        let submissions = rg.queue_submissions(&mut self.graph_cache);
+        let command_list_count = submissions
+            .last()
+            .map_or(0, |submission| submission.command_lists.end);
+
+        while self.graph_command_lists.len() < command_list_count {
+            self.graph_command_lists.push(
+                RenderCommandList::new(self.handles.clone(), 1024 * 1024 * 16, 1024 * 1024)
+                    .unwrap(),
             );
+        }
 
-            let mut allocated_resources = handle_allocator.into_allocated_resources();
-
-            frame_resources
-                .handles
-                .append(&mut allocated_resources.transient);
-
-            frame_resources
-                .handles
-                .append(&mut get_resources_pending_release());
-
-            self.persistent_resources
-                .append(&mut allocated_resources.persistent);
+        let graph_command_lists = &mut self.graph_command_lists[..command_list_count];
+        for command_list in graph_command_lists.iter_mut() {
+            command_list.reset();
+        }
 
-            execution_output.map(|execution_output| execution_output.output_texture)
-        };
+        // println!("Recorded {} passes", rg.passes.len());
+        let mut execution_output = rg.execute(
+            rg::RenderGraphExecutionParams {
+                handles: &handle_allocator,
+                device: &*device,
+                pipeline_cache,
+            },
+            &mut self.graph_cache,
+            &mut self.temporal_resources,
+            &self.dynamic_constants,
+            graph_command_lists,
+        );
 
         device.create_fence(
             resources_used_fence,
@@ -123,16 +146,66 @@ impl<'a> RenderLoop<'a> {
         )?;
 
         frame_resources.resources_used_fence = Some(resources_used_fence);
+        frame_resources.transient_pool_frame = Some(self.graph_cache.take_frame());
 
         self.dynamic_constants
             .commit_and_reset(early_command_list, device);
 
-        device.compile_command_lists(
-            command_list_handle,
-            &[early_command_list, main_command_list],
-        )?;
+        let submit_result = Self::submit_graph_command_lists(
+            device,
+            &handle_allocator,
+            early_command_list,
+            graph_command_lists,
+            &submissions,
+        );
+
+        let mut allocated_resources = handle_allocator.into_allocated_resources();
+
+        frame_resources
+            .handles
+            .append(&mut allocated_resources.transient);
+
+        frame_resources
+            .handles
+            .append(&mut get_resources_pending_release());
+
+        self.persistent_resources
+            .append(&mut allocated_resources.persistent);
+        self.persistent_memory
+            .extend(allocated_resources.persistent_memory.drain(..));
+
+        // Temporal resources replaced this frame may still be in use by frames in flight,
+        // so instead of destroying them right away, let them retire with this frame.
+        let retired_temporal_resources = self.temporal_resources.take_retired();
+        self.persistent_resources
+            .retain(|res| !retired_temporal_resources.contains(res));
+        for resource in retired_temporal_resources.iter() {
+            self.persistent_memory.remove(resource);
+        }
+        frame_resources
+            .handles
+            .extend(retired_temporal_resources.into_iter());
+
+        let mut persistent_memory: Vec<rg::PersistentAllocation> =
+            self.persistent_memory.values().cloned().collect();
+        persistent_memory.push(rg::PersistentAllocation {
+            name: "dynamic constants".to_owned(),
+            bytes: self.dynamic_constants.allocated_bytes(),
+        });
+        persistent_memory.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        self.memory_report.persistent = persistent_memory;
+
+        submit_result?;
+
+        let mut pass_errors = Vec::new();
+        if let Ok(execution_output) = &mut execution_output {
+            frame_resources.timestamp_queries = execution_output.timestamp_queries.take();
+            self.memory_report.transient = std::mem::take(&mut execution_output.memory);
+            pass_errors = std::mem::take(&mut execution_output.pass_errors);
+        }
 
-        device.submit_command_list(command_list_handle, true, None, None, None)?;
+        let output_texture =
+            execution_output.map(|execution_output| execution_output.exported_resource(tex));
 
         let result = match output_texture {
             Ok(output_texture) => {
@@ -141,7 +214,14 @@ impl<'a> RenderLoop<'a> {
                     output_texture,
                     frame_resources.resources_used_fence,
                 )?;
-                Ok(())
+
+                // The rest of the graph still ran, so the frame is presented,
+                // but the failures are reported all the same.
+                if pass_errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(rg::PassErrors(pass_errors).into())
+                }
             }
             Err(e) => {
                 device.present_swap_chain(
@@ -159,6 +239,123 @@ impl<'a> RenderLoop<'a> {
         result
     }
 
+    // Submits the early command list, which uploads dynamic constants, followed by
+    // the render graph's command lists, each submission to its queue.
+    fn submit_graph_command_lists(
+        device: &dyn RenderDevice,
+        handles: &dyn ResourceHandleAllocator,
+        early_command_list: &mut RenderCommandList<'a>,
+        graph_command_lists: &[RenderCommandList<'a>],
+        submissions: &[rg::QueueSubmission],
+    ) -> anyhow::Result<()> {
+        let create_fence = |name: String| -> anyhow::Result<RenderResourceHandle> {
+            let fence = handles.allocate_transient(RenderResourceType::Fence);
+            device.create_fence(
+                fence,
+                &RenderFenceDesc {
+                    cross_device: false,
+                },
+                name.into(),
+            )?;
+            Ok(fence)
+        };
+
+        let first_async_compute = submissions
+            .iter()
+            .position(|submission| submission.queue == rg::QueueType::AsyncCompute);
+
+        // The graphics queue executes the constant upload before anything else,
+        // but async compute needs to wait for it explicitly.
+        let constants_fence = if first_async_compute.is_some() {
+            Some(create_fence("dynamic constants fence".to_owned())?)
+        } else {
+            None
+        };
+
+        let early_command_list_handle = handles.allocate_transient(RenderResourceType::CommandList);
+        device.create_command_list(early_command_list_handle, "Early command list".into())?;
+        device.compile_command_lists(early_command_list_handle, &[early_command_list])?;
+        device.submit_command_list(early_command_list_handle, true, None, constants_fence, None)?;
+
+        let mut submission_fences: Vec<Option<RenderResourceHandle>> =
+            vec![None; submissions.len()];
+        for (submission_idx, submission) in submissions.iter().enumerate() {
+            for &wait_for in submission.wait_for.iter() {
+                submission_fences[wait_for] = Some(create_fence(format!(
+                    "submission {} -> {} fence",
+                    wait_for, submission_idx
+                ))?);
+            }
+        }
+
+        for (submission_idx, submission) in submissions.iter().enumerate() {
+            let mut wait_fences: Vec<RenderResourceHandle> = submission
+                .wait_for
+                .iter()
+                .map(|wait_for| submission_fences[*wait_for].unwrap())
+                .collect();
+
+            if Some(submission_idx) == first_async_compute {
+                wait_fences.extend(constants_fence);
+            }
+
+            let (queue, name) = match submission.queue {
+                rg::QueueType::Graphics => (RenderQueueType::Graphics, "Graphics command list"),
+                rg::QueueType::AsyncCompute => {
+                    (RenderQueueType::Compute, "Async compute command list")
+                }
+            };
+
+            let command_list_handle = handles.allocate_transient(RenderResourceType::CommandList);
+            device.create_command_list(command_list_handle, name.into())?;
+            let command_lists: Vec<&RenderCommandList<'a>> = graph_command_lists
+                [submission.command_lists.clone()]
+            .iter()
+            .collect();
+
+            device.compile_command_lists(command_list_handle, &command_lists)?;
+            device.submit_command_list(
+                command_list_handle,
+                true,
+                if wait_fences.is_empty() {
+                    None
+                } else {
+                    Some(&wait_fences)
+                },
+                submission_fences[submission_idx],
+                Some(queue),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// GPU memory used by the last frame.
+    pub fn memory_report(&self) -> &rg::MemoryReport {
+        &self.memory_report
+    }
+
+    /// Includes resources created outside of the render loop, e.g. meshes, in memory reports.
+    pub fn track_external_memory(
+        &mut self,
+        handle: RenderResourceHandle,
+        name: &str,
+        bytes: usize,
+    ) {
+        self.persistent_memory.insert(
+            handle,
+            rg::PersistentAllocation {
+                name: name.to_owned(),
+                bytes,
+            },
+        );
+    }
+
+    /// Per-pass GPU timings; profiling is disabled by default.
+    pub fn gpu_profiler(&mut self) -> &mut GpuProfiler {
+        &mut self.gpu_profiler
+    }
+
     pub fn destroy_resources(&mut self) -> std::result::Result<(), anyhow::Error> {
         let device = &mut *self.device.write()?;
 
@@ -4,7 +4,9 @@ use crate::{camera::CameraMatrices, mesh::GpuTriangleMesh, RaytraceData};
 use render_core::{
     state::{build, RenderState},
     types::{
-        RenderDrawPacket, RenderFormat, RenderResourceType, RenderShaderViewsDesc, RenderTargetInfo,
+        DepthStencilTargetInfo, RenderDrawPacket, RenderFormat, RenderLoadOp, RenderPrimitiveType,
+        RenderResourceStates, RenderResourceType, RenderShaderViewsDesc, RenderStoreOp,
+        RenderTargetInfo,
     },
 };
 use rg::{command_ext::*, resource_view::*, *};
@@ -15,33 +17,42 @@ pub fn render_frame_rg(
     camera_matrices: CameraMatrices,
     mesh: Arc<GpuTriangleMesh>,
     rt_data: RaytraceData,
-) -> (RenderGraph, Handle<Texture>) {
+) -> (RenderGraph, ExportedHandle<Texture>) {
     let mut rg = RenderGraph::new();
 
     let mut tex = synth_gradients(
         &mut rg,
-        TextureDesc {
-            width: 1280,
-            height: 720,
-            format: RenderFormat::R16g16b16a16Float,
-        },
+        TextureDesc::new_2d(1280, 720, RenderFormat::R16g16b16a16Float),
     );
 
     //raster_mesh(camera_matrices, mesh, &mut rg, &mut tex);
     test_raytrace(rt_data, &mut rg, &mut tex);
 
-    //let tex = blur(&mut rg, &tex);
-    //let tex = into_ycbcr(&mut rg, tex);
+    // Disabled features are bypassed without adding any passes.
+    let tex = rg.add_feature(&Blur { enabled: false }, tex);
+    let tex = rg.add_feature(&IntoYcbcr { enabled: false }, tex);
+
+    let tex = rg.export(
+        tex,
+        RenderResourceStates::PIXEL_SHADER_RESOURCE
+            | RenderResourceStates::NON_PIXEL_SHADER_RESOURCE,
+    );
 
     (rg, tex)
 }
 
 fn test_raytrace(rt_data: RaytraceData, rg: &mut RenderGraph, output: &mut Handle<Texture>) {
-    let mut pass = rg.add_pass();
+    let mut pass = rg.add_pass("raytrace");
     let output_ref = pass.write(output);
     let output_desc = *output_ref.desc();
 
     pass.render(move |cb, resources| {
+        let pipeline = resources.ray_tracing_pipeline(RayTracingPipelineDesc {
+            raygen_shader: "/assets/shaders/rt/triangle.rgen.hlsl".into(),
+            miss_shader: "/assets/shaders/rt/triangle.rmiss.hlsl".into(),
+            hit_shader: "/assets/shaders/rt/triangle.rchit.hlsl".into(),
+        })?;
+
         let raygen_shader_views = {
             let resource_views = RenderShaderViewsDesc {
                 shader_resource_views: vec![build::ray_tracing_acceleration(
@@ -73,8 +84,8 @@ fn test_raytrace(rt_data: RaytraceData, rg: &mut RenderGraph, output: &mut Handl
         };
 
         cb.ray_trace(
-            rt_data.pipeline_state,
-            rt_data.shader_table,
+            pipeline.pipeline_state,
+            pipeline.shader_table,
             rt_data.top_acceleration,
             &[RenderShaderArgument::new(raygen_shader_views)],
             output_desc.width,
@@ -91,20 +102,41 @@ fn raster_mesh(
     rg: &mut RenderGraph,
     output: &mut Handle<Texture>,
 ) {
-    let mut pass = rg.add_pass();
+    let mut pass = rg.add_pass("raster mesh");
     let output_ref = pass.raster(output);
 
+    let mut depth = pass.create(
+        "depth",
+        &TextureDesc::new_2d(
+            output.desc().width,
+            output.desc().height,
+            RenderFormat::D32Float,
+        ),
+    );
+    let depth_ref = pass.depth_stencil(&mut depth);
+
     pass.render(move |cb, resources| {
-        let render_target = RenderTarget::new([(output_ref, RenderTargetInfo::default())]);
+        let render_target = RenderTarget::new([(output_ref, RenderTargetInfo::default())])
+            .with_depth((
+                depth_ref,
+                DepthStencilTargetInfo {
+                    load_op: RenderLoadOp::Clear,
+                    store_op: RenderStoreOp::Discard,
+                    clear_depth: 1.0,
+                    clear_stencil: 0,
+                },
+            ));
 
         let pipeline = resources.raster_pipeline(
             RasterPipelineDesc {
                 vertex_shader: "/assets/shaders/raster_simple_vs.hlsl".into(),
                 pixel_shader: "/assets/shaders/raster_simple_ps.hlsl".into(),
                 render_state: RenderState {
-                    depth_enable: false,
+                    depth_enable: true,
                     ..Default::default()
                 },
+                vertex_streams: Vec::new(),
+                primitive_type: RenderPrimitiveType::TriangleList,
             },
             &render_target,
         )?;
@@ -119,7 +151,7 @@ fn raster_mesh(
             .dynamic_constants
             .push(Constants { camera_matrices });
 
-        cb.begin_render_pass(resources.render_pass(&render_target)?)?;
+        resources.begin_render_pass(cb, &render_target)?;
         cb.draw(
             pipeline.handle,
             &[RenderShaderArgument::new(*mesh.shader_views).constants(constants)],
@@ -130,15 +162,15 @@ fn raster_mesh(
                 ..Default::default()
             },
         )?;
-        cb.end_render_pass()?;
+        resources.end_render_pass(cb)?;
 
         Ok(())
     });
 }
 
 fn synth_gradients(rg: &mut RenderGraph, desc: TextureDesc) -> Handle<Texture> {
-    let mut pass = rg.add_pass();
-    let mut output = pass.create(&desc);
+    let mut pass = rg.add_pass("synth gradients");
+    let mut output = pass.create("gradients", &desc);
     let output_ref = pass.write(&mut output);
 
     pass.render(move |cb, resources| {
@@ -150,53 +182,94 @@ fn synth_gradients(rg: &mut RenderGraph, desc: TextureDesc) -> Handle<Texture> {
                 resources,
                 &[],
                 &[("output_tex", uav::texture_2d(output_ref))],
-            ))],
+            )?)],
         )
     });
 
     output
 }
 
-fn blur(rg: &mut RenderGraph, input: &Handle<Texture>) -> Handle<Texture> {
-    let mut pass = rg.add_pass();
-    let input_ref = pass.read(input);
+pub struct Blur {
+    pub enabled: bool,
+}
 
-    let mut output = pass.create(input.desc());
-    let output_ref = pass.write(&mut output);
+impl RenderFeature for Blur {
+    type Inputs = Handle<Texture>;
+    type Outputs = Handle<Texture>;
 
-    pass.render(move |cb, resources| {
-        let pipeline = resources.compute_pipeline("/assets/shaders/blur.hlsl")?;
-        cb.rg_dispatch_2d(
-            &pipeline,
-            input_ref.desc().dims(),
-            &[RenderShaderArgument::new(pipeline.named_views(
-                resources,
-                &[("input_tex", srv::texture_2d(input_ref))],
-                &[("output_tex", uav::texture_2d(output_ref))],
-            ))],
-        )
-    });
+    fn name(&self) -> &str {
+        "blur"
+    }
 
-    output
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn add_passes(&self, rg: &mut RenderGraph, input: Handle<Texture>) -> Handle<Texture> {
+        let mut pass = rg.add_pass("blur");
+        let input_ref = pass.read(&input);
+
+        let mut output = pass.create("blurred", input.desc());
+        let output_ref = pass.write(&mut output);
+
+        pass.render(move |cb, resources| {
+            let pipeline = resources.compute_pipeline("/assets/shaders/blur.hlsl")?;
+            cb.rg_dispatch_2d(
+                &pipeline,
+                input_ref.desc().dims(),
+                &[RenderShaderArgument::new(pipeline.named_views(
+                    resources,
+                    &[("input_tex", srv::texture_2d(input_ref))],
+                    &[("output_tex", uav::texture_2d(output_ref))],
+                )?)],
+            )
+        });
+
+        output
+    }
+
+    fn bypass(&self, input: Handle<Texture>) -> Handle<Texture> {
+        input
+    }
 }
 
-#[allow(dead_code)]
-fn into_ycbcr(rg: &mut RenderGraph, mut input: Handle<Texture>) -> Handle<Texture> {
-    let mut pass = rg.add_pass();
-    let input_ref = pass.write(&mut input);
+pub struct IntoYcbcr {
+    pub enabled: bool,
+}
 
-    pass.render(move |cb, resources| {
-        let pipeline = resources.compute_pipeline("/assets/shaders/into_ycbcr.hlsl")?;
-        cb.rg_dispatch_2d(
-            &pipeline,
-            input_ref.desc().dims(),
-            &[RenderShaderArgument::new(pipeline.named_views(
-                resources,
-                &[],
-                &[("input_tex", uav::texture_2d(input_ref))],
-            ))],
-        )
-    });
+impl RenderFeature for IntoYcbcr {
+    type Inputs = Handle<Texture>;
+    type Outputs = Handle<Texture>;
+
+    fn name(&self) -> &str {
+        "into ycbcr"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn add_passes(&self, rg: &mut RenderGraph, mut input: Handle<Texture>) -> Handle<Texture> {
+        let mut pass = rg.add_pass("convert");
+        let input_ref = pass.write(&mut input);
+
+        pass.render(move |cb, resources| {
+            let pipeline = resources.compute_pipeline("/assets/shaders/into_ycbcr.hlsl")?;
+            cb.rg_dispatch_2d(
+                &pipeline,
+                input_ref.desc().dims(),
+                &[RenderShaderArgument::new(pipeline.named_views(
+                    resources,
+                    &[],
+                    &[("input_tex", uav::texture_2d(input_ref))],
+                )?)],
+            )
+        });
+
+        input
+    }
 
-    input
+    fn bypass(&self, input: Handle<Texture>) -> Handle<Texture> {
+        input
+    }
 }
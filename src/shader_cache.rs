@@ -1,4 +1,4 @@
-use render_core::types::*;
+use render_core::{constants::MAX_RAY_TRACING_SHADER_TYPE, types::*};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -6,10 +6,16 @@ use std::{
 };
 use turbosloth::*;
 
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+enum ShaderKind {
+    Shader(RenderShaderType),
+    RayTracingProgram(RayTracingProgramType),
+}
+
 #[derive(Hash, PartialEq, Eq)]
 struct ShaderCacheKey {
     path: PathBuf,
-    shader_type: RenderShaderType,
+    kind: ShaderKind,
 }
 
 struct TurboslothShaderCacheEntry {
@@ -35,10 +41,15 @@ impl TurboslothShaderCache {
     fn compile_shader(
         &self,
         params: &rg::RenderGraphExecutionParams<'_, '_, '_>,
-        shader_type: RenderShaderType,
+        kind: ShaderKind,
         path: &Path,
     ) -> anyhow::Result<TurboslothShaderCacheEntry> {
-        let path = path;
+        let shader_type = match kind {
+            ShaderKind::Shader(shader_type) => shader_type,
+            ShaderKind::RayTracingProgram(program_type) => {
+                return self.compile_ray_tracing_program(params, program_type, path);
+            }
+        };
 
         match shader_type {
             RenderShaderType::Vertex
@@ -73,6 +84,18 @@ impl TurboslothShaderCache {
                         rg::shader_cache::RasterShaderCacheEntry {
                             shader_handle,
                             stage: shader_type,
+                            srvs: shader_data.srvs.clone(),
+                            uavs: shader_data.uavs.clone(),
+                            cbuffers: shader_data.cbuffers.clone(),
+                            vertex_inputs: shader_data
+                                .vertex_inputs
+                                .iter()
+                                .map(|input| rg::shader_cache::ShaderVertexInput {
+                                    semantic: input.semantic.clone(),
+                                    location: input.location,
+                                    component_count: input.component_count,
+                                })
+                                .collect(),
                         },
                     )),
                 })
@@ -105,6 +128,7 @@ impl TurboslothShaderCache {
                             shader_handle,
                             srvs: shader_data.srvs.clone(),
                             uavs: shader_data.uavs.clone(),
+                            cbuffers: shader_data.cbuffers.clone(),
                             group_size: shader_data.group_size,
                         },
                     )),
@@ -113,16 +137,69 @@ impl TurboslothShaderCache {
         }
     }
 
+    fn compile_ray_tracing_program(
+        &self,
+        params: &rg::RenderGraphExecutionParams<'_, '_, '_>,
+        program_type: RayTracingProgramType,
+        path: &Path,
+    ) -> anyhow::Result<TurboslothShaderCacheEntry> {
+        let lazy_shader = crate::shader_compiler::CompileRayTracingShader {
+            path: path.to_owned(),
+        }
+        .into_lazy();
+
+        let shader_data = smol::block_on(lazy_shader.eval(&self.lazy_cache))?;
+
+        // TODO: more shaders
+        let shader_type = match program_type {
+            RayTracingProgramType::RayGen => RayTracingShaderType::RayGen,
+            RayTracingProgramType::Miss => RayTracingShaderType::Miss,
+            RayTracingProgramType::Hit => RayTracingShaderType::ClosestHit,
+        };
+
+        let program_handle = params
+            .handles
+            .allocate_persistent(RenderResourceType::RayTracingProgram);
+
+        params.device.create_ray_tracing_program(
+            program_handle,
+            &RayTracingProgramDesc {
+                program_type,
+                shaders: {
+                    let mut shaders: [_; MAX_RAY_TRACING_SHADER_TYPE] =
+                        array_init::array_init(|_| None);
+                    shaders[shader_type as usize] = Some(RayTracingShaderDesc {
+                        entry_point: "main".to_owned(),
+                        shader_data: shader_data.spirv.clone(),
+                    });
+                    shaders
+                },
+                signature: RenderShaderSignatureDesc::default(), // TODO
+            },
+            format!("{} program", shader_data.name).into(),
+        )?;
+
+        Ok(TurboslothShaderCacheEntry {
+            lazy_handle: lazy_shader.into_opaque(),
+            entry: Arc::new(rg::shader_cache::ShaderCacheEntry::RayTracing(
+                rg::shader_cache::RayTracingShaderCacheEntry {
+                    shader_handle: program_handle,
+                    program_type,
+                },
+            )),
+        })
+    }
+
     fn get_or_load_impl(
         &self,
         params: &rg::RenderGraphExecutionParams<'_, '_, '_>,
-        shader_type: RenderShaderType,
+        kind: ShaderKind,
         path: &Path,
         retired: &mut Option<Arc<rg::shader_cache::ShaderCacheEntry>>,
     ) -> anyhow::Result<Arc<rg::shader_cache::ShaderCacheEntry>> {
         let key = ShaderCacheKey {
             path: path.to_owned(),
-            shader_type,
+            kind,
         };
 
         let mut shaders = self.shaders.write().unwrap();
@@ -136,7 +213,7 @@ impl TurboslothShaderCache {
             }
         }
 
-        let new_entry = self.compile_shader(params, shader_type, path)?;
+        let new_entry = self.compile_shader(params, kind, path)?;
         let result = new_entry.entry.clone();
         shaders.insert(key, new_entry);
         Ok(result)
@@ -151,7 +228,24 @@ impl rg::shader_cache::ShaderCache for TurboslothShaderCache {
         path: &Path,
     ) -> rg::shader_cache::ShaderCacheOutput {
         let mut retired = None;
-        let entry = self.get_or_load_impl(params, shader_type, path, &mut retired);
+        let entry =
+            self.get_or_load_impl(params, ShaderKind::Shader(shader_type), path, &mut retired);
+        rg::shader_cache::ShaderCacheOutput { entry, retired }
+    }
+
+    fn get_or_load_ray_tracing(
+        &self,
+        params: &rg::RenderGraphExecutionParams<'_, '_, '_>,
+        program_type: RayTracingProgramType,
+        path: &Path,
+    ) -> rg::shader_cache::ShaderCacheOutput {
+        let mut retired = None;
+        let entry = self.get_or_load_impl(
+            params,
+            ShaderKind::RayTracingProgram(program_type),
+            path,
+            &mut retired,
+        );
         rg::shader_cache::ShaderCacheOutput { entry, retired }
     }
 }
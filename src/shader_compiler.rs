@@ -55,6 +55,7 @@ pub struct ComputeShader {
     pub spirv: Vec<u8>,
     pub srvs: Vec<String>,
     pub uavs: Vec<String>,
+    pub cbuffers: Vec<String>,
 }
 
 fn compile_cs_hlsl_impl(
@@ -86,31 +87,11 @@ fn compile_cs_hlsl_impl(
 
     let spirv = refl.get_code();
     let local_size = get_cs_local_size_from_spirv(&spirv)?;
-
-    let mut srvs = Vec::new();
-    let mut uavs = Vec::new();
-
-    let descriptor_sets = refl.enumerate_descriptor_sets(None).unwrap();
-    {
-        let descriptor_set = &descriptor_sets[0].value;
-        for binding in descriptor_set.binding_refs.iter() {
-            let binding = &binding.value;
-
-            assert_ne!(
-                binding.resource_type,
-                spirv_reflect::types::resource::ReflectResourceTypeFlags::UNDEFINED
-            );
-            match binding.resource_type {
-                spirv_reflect::types::resource::ReflectResourceTypeFlags::SHADER_RESOURCE_VIEW => {
-                    srvs.push(binding.name.clone());
-                }
-                spirv_reflect::types::resource::ReflectResourceTypeFlags::UNORDERED_ACCESS_VIEW => {
-                    uavs.push(binding.name.clone());
-                }
-                _ => {}
-            };
-        }
-    }
+    let ShaderBindings {
+        srvs,
+        uavs,
+        cbuffers,
+    } = reflect_shader_bindings(&refl)?;
 
     Ok(ComputeShader {
         name,
@@ -118,6 +99,7 @@ fn compile_cs_hlsl_impl(
         spirv: spirv.to_owned().into_byte_vec(),
         srvs,
         uavs,
+        cbuffers,
     })
 }
 
@@ -125,6 +107,16 @@ pub struct RasterShader {
     pub name: String,
     pub stage: RenderShaderType,
     pub spirv: Vec<u8>,
+    pub srvs: Vec<String>,
+    pub uavs: Vec<String>,
+    pub cbuffers: Vec<String>,
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+pub struct VertexInput {
+    pub semantic: String,
+    pub location: u32,
+    pub component_count: u32,
 }
 
 #[derive(Clone, Hash)]
@@ -170,12 +162,41 @@ impl LazyWorker for CompileRasterShader {
                     RenderShaderType::Compute => unreachable!(),
                 };
 
-                let spirv = compile_generic_shader_hlsl_impl(&name, &source, target_profile)?;
+                let spirv = compile_generic_shader_hlsl_impl(&name, &source, target_profile, &[])?;
+
+                use byte_slice_cast::*;
+                let refl = reflect_spirv_shader(spirv.as_slice_of::<u32>()?)?;
+                let ShaderBindings {
+                    srvs,
+                    uavs,
+                    cbuffers,
+                } = reflect_shader_bindings(&refl)?;
+
+                // Semantics are only kept in the SPIR-V with reflection decorations, which
+                // need VK_GOOGLE_hlsl_functionality1 on the driver side. Reflect them from
+                // a separate compile, so that the driver never sees the decorations.
+                let vertex_inputs = if self.stage == RenderShaderType::Vertex {
+                    let reflection_spirv = compile_generic_shader_hlsl_impl(
+                        &name,
+                        &source,
+                        target_profile,
+                        &["-fspv-reflect"],
+                    )?;
+                    reflect_vertex_inputs(&reflect_spirv_shader(
+                        reflection_spirv.as_slice_of::<u32>()?,
+                    )?)?
+                } else {
+                    Vec::new()
+                };
 
                 Ok(RasterShader {
                     name,
                     stage: self.stage,
                     spirv,
+                    srvs,
+                    uavs,
+                    cbuffers,
+                    vertex_inputs,
                 })
             }
             _ => anyhow::bail!("Unrecognized shader file extension: {}", ext),
@@ -222,7 +243,7 @@ impl LazyWorker for CompileRayTracingShader {
             "glsl" => unimplemented!(),
             "hlsl" => {
                 let target_profile = "lib_6_4";
-                let spirv = compile_generic_shader_hlsl_impl(&name, &source, target_profile)?;
+                let spirv = compile_generic_shader_hlsl_impl(&name, &source, target_profile, &[])?;
 
                 Ok(RayTracingShader { name, spirv })
             }
@@ -273,6 +294,82 @@ fn reflect_spirv_shader(shader_code: &[u32]) -> Result<spirv_reflect::ShaderModu
     res
 }
 
+// Names of the views and constant buffers a shader binds, in binding order
+struct ShaderBindings {
+    srvs: Vec<String>,
+    uavs: Vec<String>,
+    cbuffers: Vec<String>,
+}
+
+fn reflect_shader_bindings(refl: &spirv_reflect::ShaderModule) -> Result<ShaderBindings> {
+    let mut srvs = Vec::new();
+    let mut uavs = Vec::new();
+    let mut cbuffers = Vec::new();
+
+    let descriptor_sets = convert_spirv_reflect_err(refl.enumerate_descriptor_sets(None))?;
+
+    // Shaders which don't bind any views, e.g. simple pixel shaders, have no descriptor sets.
+    if let Some(descriptor_set) = descriptor_sets.first() {
+        let descriptor_set = &descriptor_set.value;
+        for binding in descriptor_set.binding_refs.iter() {
+            let binding = &binding.value;
+
+            if binding.resource_type
+                == spirv_reflect::types::resource::ReflectResourceTypeFlags::UNDEFINED
+            {
+                bail!(
+                    "Binding {} ({:?}) has an undefined resource type",
+                    binding.binding,
+                    binding.name
+                );
+            }
+
+            match binding.resource_type {
+                spirv_reflect::types::resource::ReflectResourceTypeFlags::SHADER_RESOURCE_VIEW => {
+                    srvs.push(binding.name.clone());
+                }
+                spirv_reflect::types::resource::ReflectResourceTypeFlags::UNORDERED_ACCESS_VIEW => {
+                    uavs.push(binding.name.clone());
+                }
+                spirv_reflect::types::resource::ReflectResourceTypeFlags::CONSTANT_BUFFER_VIEW => {
+                    cbuffers.push(binding.name.clone());
+                }
+                _ => {}
+            };
+        }
+    }
+
+    Ok(ShaderBindings {
+        srvs,
+        uavs,
+        cbuffers,
+    })
+}
+
+fn reflect_vertex_inputs(refl: &spirv_reflect::ShaderModule) -> Result<Vec<VertexInput>> {
+    let variables = convert_spirv_reflect_err(refl.enumerate_input_variables(None))?;
+
+    variables
+        .into_iter()
+        .filter(|var| {
+            !var.decoration_flags
+                .contains(spirv_reflect::types::ReflectDecorationFlags::BUILT_IN)
+        })
+        .map(|var| {
+            // From the `UserSemantic` decoration emitted with `-fspv-reflect`
+            if var.semantic.is_empty() {
+                bail!("Vertex input {:?} has no semantic", var.name);
+            }
+
+            Ok(VertexInput {
+                semantic: var.semantic,
+                location: var.location,
+                component_count: var.numeric.vector.component_count.max(1),
+            })
+        })
+        .collect()
+}
+
 fn get_cs_local_size_from_spirv(spirv: &[u32]) -> Result<[u32; 3]> {
     let mut loader = rspirv::dr::Loader::new();
     rspirv::binary::parse_words(spirv, &mut loader).unwrap();
@@ -306,22 +403,19 @@ fn compile_generic_shader_hlsl_impl(
     name: &str,
     source: &[shader_prepper::SourceChunk],
     target_profile: &str,
+    extra_args: &[&str],
 ) -> Result<Vec<u8>> {
     let mut source_text = String::new();
     for s in source {
         source_text += &s.source;
     }
 
+    let mut args = vec!["-spirv", "-fspv-target-env=vulkan1.2"];
+    args.extend_from_slice(extra_args);
+
     let t0 = std::time::Instant::now();
-    let spirv = hassle_rs::compile_hlsl(
-        &name,
-        &source_text,
-        "main",
-        target_profile,
-        &["-spirv", "-fspv-target-env=vulkan1.2"],
-        &[],
-    )
-    .map_err(|err| anyhow!("{}", err))?;
+    let spirv = hassle_rs::compile_hlsl(&name, &source_text, "main", target_profile, &args, &[])
+        .map_err(|err| anyhow!("{}", err))?;
     println!("dxc took {:?} for {}", t0.elapsed(), name);
 
     Ok(spirv)